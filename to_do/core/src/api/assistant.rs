@@ -0,0 +1,189 @@
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
+use std::collections::HashMap;
+use std::env;
+
+use crate::structs::base::Base;
+use crate::dates::format_date;
+use crate::enums::TaskStatus;
+use crate::errors::TaskError;
+
+/// Replies taking longer than this are given up on.
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Patterns always replaced before anything leaves the machine: email
+/// addresses, URLs and runs of six or more digits (phone, account and card
+/// numbers).
+const DEFAULT_REDACTIONS: [&str; 3] = [
+    r"[\w.+-]+@[\w-]+(\.[\w-]+)+",
+    r"https?://\S+",
+    r"\d[\d -]{4,}\d",
+];
+
+/// Where and how the assistant sends its requests.
+///
+/// Nothing is sent unless both `TODO_ASSISTANT_URL` and
+/// `TODO_ASSISTANT_MODEL` are set, on top of the binary being built with
+/// the `assistant` feature.
+pub struct AssistantConfig {
+    /// Base URL of an OpenAI-compatible API, e.g. `https://api.openai.com/v1`.
+    pub url: String,
+    pub model: String,
+    api_key: Option<String>,
+    redactions: Vec<Regex>,
+}
+
+impl AssistantConfig {
+    /// Reads the configuration from `TODO_ASSISTANT_URL`,
+    /// `TODO_ASSISTANT_MODEL`, `TODO_ASSISTANT_API_KEY` (optional) and
+    /// `TODO_ASSISTANT_REDACT` (optional; a regex whose matches are
+    /// redacted on top of the defaults).
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(AssistantConfig)` - The configuration
+    /// * `Err(TaskError::Assistant)` - If the URL or model isn't set
+    /// * `Err(TaskError::InvalidRegex)` - If `TODO_ASSISTANT_REDACT` isn't a valid regex
+    pub fn from_env() -> Result<AssistantConfig, TaskError> {
+        let required = |var: &str| env::var(var)
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .ok_or_else(|| TaskError::Assistant(format!("{} is not set; the assistant stays off until it is", var)));
+        let mut patterns: Vec<String> = DEFAULT_REDACTIONS.iter().map(|pattern| pattern.to_string()).collect();
+        if let Ok(extra) = env::var("TODO_ASSISTANT_REDACT")
+            && !extra.is_empty()
+        {
+            patterns.push(extra);
+        }
+        let redactions = patterns.iter()
+            .map(|pattern| Regex::new(pattern).map_err(|e| TaskError::InvalidRegex(e.to_string())))
+            .collect::<Result<_, _>>()?;
+        Ok(AssistantConfig {
+            url: required("TODO_ASSISTANT_URL")?,
+            model: required("TODO_ASSISTANT_MODEL")?,
+            api_key: env::var("TODO_ASSISTANT_API_KEY").ok().filter(|key| !key.is_empty()),
+            redactions,
+        })
+    }
+
+    /// Replaces every match of the redaction patterns in `text`.
+    pub fn redact(&self, text: &str) -> String {
+        self.redactions.iter().fold(text.to_string(), |text, pattern| {
+            pattern.replace_all(&text, "[redacted]").into_owned()
+        })
+    }
+
+    /// Sends `prompt` to the chat completions endpoint.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(String)` - The assistant's reply
+    /// * `Err(TaskError::Assistant)` - If the request fails or the reply has no message
+    pub fn ask(&self, prompt: &Prompt) -> Result<String, TaskError> {
+        let body = serde_json::json!({
+            "model": self.model,
+            "messages": [
+                { "role": "system", "content": prompt.system },
+                { "role": "user", "content": prompt.user },
+            ],
+        });
+        let agent: ureq::Agent = ureq::Agent::config_builder().timeout_global(Some(TIMEOUT)).build().into();
+        let url = format!("{}/chat/completions", self.url.trim_end_matches('/'));
+        let mut request = agent.post(&url);
+        if let Some(key) = &self.api_key {
+            request = request.header("Authorization", &format!("Bearer {}", key));
+        }
+        let failed = |e: ureq::Error| TaskError::Assistant(format!("Error calling the assistant at {}: {}", url, e));
+        let reply: serde_json::Value = request.send_json(&body)
+            .map_err(failed)?
+            .body_mut()
+            .read_json()
+            .map_err(failed)?;
+        reply["choices"][0]["message"]["content"]
+            .as_str()
+            .map(|content| content.trim().to_string())
+            .ok_or_else(|| TaskError::Assistant("The assistant's reply has no message".to_string()))
+    }
+}
+
+/// The instructions and the task data sent to the assistant. Only `user`
+/// carries task data; the prompt builders redact the titles and
+/// descriptions they put in it.
+pub struct Prompt {
+    pub system: String,
+    pub user: String,
+}
+
+/// Asks for a short summary of a task's description.
+///
+/// # Returns
+///
+/// * `Ok(Prompt)` - The prompt
+/// * `Err(TaskError::Assistant)` - If the task has no description
+pub fn summarize_prompt(config: &AssistantConfig, task: &Base) -> Result<Prompt, TaskError> {
+    let description = task.description.as_deref()
+        .ok_or_else(|| TaskError::Assistant(format!("Task {} has no description to summarize", task.id)))?;
+    Ok(Prompt {
+        system: "Summarize the task description in at most three sentences. Reply with the summary only.".to_string(),
+        user: format!("Title: {}\n\n{}", config.redact(&task.title), config.redact(description)),
+    })
+}
+
+/// Asks for a list of subtasks, one per line.
+pub fn breakdown_prompt(config: &AssistantConfig, task: &Base) -> Prompt {
+    let mut user = format!("Title: {}", config.redact(&task.title));
+    if let Some(description) = &task.description {
+        user.push_str(&format!("\n\n{}", config.redact(description)));
+    }
+    Prompt {
+        system: "Break the task into 3 to 7 concrete subtasks. Reply with one short subtask title per line and nothing else.".to_string(),
+        user,
+    }
+}
+
+/// Asks for a weekly review of the tasks done or created in the last seven
+/// days and the open ones that are overdue or due within the next seven.
+///
+/// Tasks don't record when they were completed, so done tasks count as
+/// "this week" when they were created or due in the last seven days.
+pub fn review_prompt(config: &AssistantConfig, tasks: &HashMap<String, Base>, now: DateTime<Utc>) -> Prompt {
+    let week_ago = now - Duration::days(7);
+    let next_week = now + Duration::days(7);
+    let recent = |date: Option<DateTime<Utc>>| date.is_some_and(|date| date >= week_ago && date <= now);
+    let mut done: Vec<&Base> = tasks.values()
+        .filter(|task| task.status == TaskStatus::DONE && (recent(task.created_at) || recent(task.due)))
+        .collect();
+    let mut open: Vec<&Base> = tasks.values()
+        .filter(|task| task.status == TaskStatus::PENDING && task.due.is_some_and(|due| due <= next_week))
+        .collect();
+    done.sort_by(|a, b| a.title.cmp(&b.title));
+    open.sort_by_key(|task| task.due);
+    let mut user = format!("Today is {}.\n\nDone this week:\n", now.format("%Y-%m-%d"));
+    for task in &done {
+        user.push_str(&format!("- {}\n", config.redact(&task.title)));
+    }
+    user.push_str("\nOpen, overdue or due within a week:\n");
+    for task in &open {
+        user.push_str(&format!("- {} (due {}, {})\n", config.redact(&task.title), format_date(task.due), task.priority));
+    }
+    Prompt {
+        system: "Draft a short weekly review: what got done, what is at risk, and three priorities for next week.".to_string(),
+        user,
+    }
+}
+
+/// Reads subtask titles from a reply, one per line, dropping list markers
+/// such as `-`, `*` and `1.`.
+pub fn parse_subtasks(reply: &str) -> Vec<String> {
+    reply.lines()
+        .map(|line| {
+            let line = line.trim().trim_start_matches(['-', '*', '•']).trim_start();
+            let unnumbered = line.trim_start_matches(|c: char| c.is_ascii_digit());
+            match unnumbered.strip_prefix(['.', ')']) {
+                Some(rest) if unnumbered.len() < line.len() => rest.trim().to_string(),
+                _ => line.to_string(),
+            }
+        })
+        .filter(|line| !line.is_empty())
+        .collect()
+}
@@ -0,0 +1,160 @@
+use chrono::Utc;
+use dal::storage::Storage;
+use std::collections::HashMap;
+use std::env;
+
+use crate::structs::base::{Base, HistoryEntry};
+use crate::dates::{format_date, DISPLAY_FORMAT};
+use crate::errors::TaskError;
+use super::basic_actions::creates::ItemTypes;
+use super::basic_actions::gets::find;
+
+/// The user changes are attributed to: `TODO_USER`, else `USER`, else
+/// `unknown`.
+pub fn current_user() -> String {
+    env::var("TODO_USER")
+        .or_else(|_| env::var("USER"))
+        .ok()
+        .filter(|user| !user.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Describes how `after` differs from `before`, one line per changed field.
+/// A task that didn't exist before is described as created.
+pub fn describe_changes(before: Option<&Base>, after: &Base) -> Vec<String> {
+    let Some(before) = before else {
+        return vec!["created".to_string()];
+    };
+    let mut changes = Vec::new();
+    let show = |value: Option<&String>| value.map_or("-".to_string(), |value| format!("{:?}", value));
+    if before.title != after.title {
+        changes.push(format!("title {:?} -> {:?}", before.title, after.title));
+    }
+    match (&before.description, &after.description) {
+        (None, Some(_)) => changes.push("description added".to_string()),
+        (Some(_), None) => changes.push("description cleared".to_string()),
+        (Some(old), Some(new)) if old != new => changes.push("description changed".to_string()),
+        _ => {}
+    }
+    if before.status != after.status {
+        changes.push(format!("status {} -> {}", before.status, after.status));
+    }
+    if before.priority != after.priority {
+        changes.push(format!("priority {} -> {}", before.priority, after.priority));
+    }
+    if before.due != after.due {
+        changes.push(format!("due {} -> {}", format_date(before.due), format_date(after.due)));
+    }
+    if before.parent_id != after.parent_id {
+        changes.push(format!("parent {} -> {}", show(before.parent_id.as_ref()), show(after.parent_id.as_ref())));
+    }
+    if before.recurrence != after.recurrence {
+        changes.push(format!("repeats {} -> {}", show(before.recurrence.as_ref()), show(after.recurrence.as_ref())));
+    }
+    let added: Vec<&String> = after.tags.iter().filter(|tag| !before.tags.contains(tag)).collect();
+    let removed: Vec<&String> = before.tags.iter().filter(|tag| !after.tags.contains(tag)).collect();
+    if !added.is_empty() || !removed.is_empty() {
+        let tags: Vec<String> = added.iter().map(|tag| format!("+{}", tag))
+            .chain(removed.iter().map(|tag| format!("-{}", tag)))
+            .collect();
+        changes.push(format!("tags {}", tags.join(" ")));
+    }
+    changes
+}
+
+/// Appends a history entry to every task that a command created or changed.
+///
+/// # Arguments
+///
+/// * `before` - The tasks the command wrote, as they were before it ran (`None` for new ones)
+///
+/// # Returns
+///
+/// * `Ok(HashMap<String, Option<Base>>)` - Those tasks as stored now, including the new entries; `None` if deleted
+/// * `Err(TaskError)` - If the store can't be read or written
+pub fn annotate(store: &dyn Storage<Base>, before: &HashMap<String, Option<Base>>) -> Result<HashMap<String, Option<Base>>, TaskError> {
+    let by = current_user();
+    let at = Utc::now();
+    let mut after = HashMap::new();
+    for (id, previous) in before {
+        let mut current = find(store, id)?;
+        if let Some(task) = current.as_mut()
+            && previous.as_ref() != Some(&*task)
+        {
+            let changes = describe_changes(previous.as_ref(), task);
+            if !changes.is_empty() {
+                task.history.extend(changes.into_iter().map(|change| HistoryEntry { at, by: by.clone(), change }));
+                store.save_one(id, task)?;
+            }
+        }
+        after.insert(id.clone(), current);
+    }
+    Ok(after)
+}
+
+/// Renders a task's history, oldest first, one change per line.
+pub fn render_history(item: &ItemTypes) -> String {
+    let history = &item.base().history;
+    if history.is_empty() {
+        return format!("{}\nNo recorded changes\n", item);
+    }
+    let width = history.iter().map(|entry| entry.by.chars().count()).max().unwrap_or(0);
+    let mut out = format!("{}\n", item);
+    for entry in history {
+        out.push_str(&format!("{}  {:<width$}  {}\n", entry.at.format(DISPLAY_FORMAT), entry.by, entry.change, width = width));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::{Priority, TaskStatus};
+    use crate::testing::{store_with, task};
+
+    #[test]
+    fn each_changed_field_is_one_line() {
+        let before = Base { tags: vec!["home".to_string()], ..task("1", "Buy milk") };
+        let after = Base {
+            title: "Buy oat milk".to_string(),
+            status: TaskStatus::DONE,
+            priority: Priority::High,
+            tags: vec!["shop".to_string()],
+            ..before.clone()
+        };
+        assert_eq!(describe_changes(Some(&before), &after), [
+            "title \"Buy milk\" -> \"Buy oat milk\"",
+            "status PENDING -> DONE",
+            "priority MEDIUM -> HIGH",
+            "tags +shop -home",
+        ]);
+        assert!(describe_changes(Some(&before), &before).is_empty());
+        assert_eq!(describe_changes(None, &after), ["created"]);
+    }
+
+    #[test]
+    fn only_tasks_that_changed_are_annotated() {
+        let untouched = task("2", "Call mum");
+        let store = store_with("audit-annotate", vec![task("1", "Buy milk"), untouched.clone(), task("4", "Gone")]);
+        let before = HashMap::from([
+            ("1".to_string(), Some(task("1", "Buy milk"))),
+            ("2".to_string(), Some(untouched.clone())),
+            ("3".to_string(), None),
+            ("4".to_string(), Some(task("4", "Gone"))),
+        ]);
+        store.save_one("1", &Base { priority: Priority::Urgent, due: Some(Utc::now()), ..task("1", "Buy milk") }).unwrap();
+        store.save_one("3", &task("3", "New")).unwrap();
+        store.delete_one("4").unwrap();
+
+        let after = annotate(&*store, &before).unwrap();
+        let history = |id: &str| store.get_one(id).unwrap().history.into_iter().map(|entry| entry.change).collect::<Vec<_>>();
+        let changed = history("1");
+        assert_eq!(changed.len(), 2);
+        assert!(changed[0].starts_with("priority MEDIUM -> URGENT"));
+        assert!(changed[1].starts_with("due - -> "));
+        assert_eq!(history("3"), ["created"]);
+        assert_eq!(store.get_one("2").unwrap(), untouched);
+        assert_eq!(after["4"], None);
+        assert_eq!(after["1"].as_ref().map(|task| task.history.len()), Some(2));
+    }
+}
@@ -1,35 +1,202 @@
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
 use std::fmt;
-use dal::json_file::save_one;
+use dal::errors::DalError;
+use dal::ids::IdScheme;
+use dal::storage::Storage;
 
 use crate::structs::{
+    base::Base,
     done::Done,
     pending::Pending,
 };
 
-use crate::enums::TaskStatus;
+use crate::enums::{Priority, TaskStatus};
+use crate::errors::TaskError;
+use crate::normalize::{match_key, normalize_description, normalize_title};
+use crate::hooks::{self, HookAction, HookStage};
+use crate::recurrence::Recurrence;
+use crate::api::tags::{suggest_tags, TagSuggestion};
+
+/// How many ids `insert` tries before giving up, when each one turns out
+/// to be taken already.
+const MAX_ID_ATTEMPTS: usize = 1000;
+
+/// How many tags `create` suggests at most.
+const MAX_TAG_SUGGESTIONS: usize = 3;
 
 pub enum ItemTypes {
     Done(Done),
     Pending(Pending),
 }
 
+impl ItemTypes {
+    /// Wraps a stored task in the variant matching its status.
+    pub fn from_base(base: Base) -> Self {
+        match base.status {
+            TaskStatus::PENDING => ItemTypes::Pending(Pending { super_struct: base }),
+            TaskStatus::DONE => ItemTypes::Done(Done { super_struct: base }),
+        }
+    }
+
+    /// The stored task shared by both variants.
+    pub fn base(&self) -> &Base {
+        match self {
+            ItemTypes::Done(done) => &done.super_struct,
+            ItemTypes::Pending(pending) => &pending.super_struct,
+        }
+    }
+
+    /// Mutable access to the stored task shared by both variants.
+    pub fn base_mut(&mut self) -> &mut Base {
+        match self {
+            ItemTypes::Done(done) => &mut done.super_struct,
+            ItemTypes::Pending(pending) => &mut pending.super_struct,
+        }
+    }
+}
+
 impl fmt::Display for ItemTypes {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            ItemTypes::Done(done) => write!(f, "{}", done.super_struct.title),
-            ItemTypes::Pending(pending) => write!(f, "{}", pending.super_struct.title),
+        let base = self.base();
+        write!(f, "{} {}", base.id, base.title)
+    }
+}
+
+/// The fields a new task is created with.
+pub struct NewTask {
+    pub title: String,
+    pub description: Option<String>,
+    pub status: TaskStatus,
+    pub priority: Priority,
+    pub due: Option<DateTime<Utc>>,
+    pub tags: Vec<String>,
+    /// Create it as a subtask of this task.
+    pub parent_id: Option<String>,
+    /// How the task repeats, in a form `Recurrence::parse` accepts.
+    pub recurrence: Option<String>,
+    /// The recurring task it's the next instance of.
+    pub previous_id: Option<String>,
+}
+
+/// A newly created task, with tags suggested for it.
+pub struct Created {
+    pub item: ItemTypes,
+    /// A stored task whose title only differs in case, accents or spacing.
+    pub similar: Option<Base>,
+    /// Tags used on stored tasks with similar titles (see `suggest_tags`), best first.
+    pub tag_suggestions: Vec<TagSuggestion>,
+}
+
+impl fmt::Display for Created {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.item)
+    }
+}
+
+/// Creates a task under a newly generated id.
+///
+/// The id comes from the scheme in `ID_SCHEME` (UUIDv7 by default). Titles
+/// are normalized but don't have to be unique; a stored task whose title
+/// only differs in case, accents or spacing is returned along with the new
+/// one, for the caller to warn about.
+///
+/// The `pre-create` and `post-create` hooks run around the write; a failing
+/// pre hook aborts the creation.
+///
+/// # Returns
+///
+/// * `Ok(Created)` - The new task, any similar one, and up to three tags suggested for it
+/// * `Err(TaskError)` - If the task is invalid, a hook fails or the store can't be written
+pub fn create(store: &dyn Storage<Base>, new_task: NewTask) -> Result<Created, TaskError> {
+    let tasks = store.get_all()?;
+    let key = match_key(&new_task.title);
+    let similar = tasks.values().find(|task| match_key(&task.title) == key).cloned();
+    let item = insert(store, &tasks, new_task)?;
+    let tag_suggestions = suggest_tags(&tasks, &item.base().title, &item.base().tags, MAX_TAG_SUGGESTIONS);
+    Ok(Created { item, similar, tag_suggestions })
+}
+
+/// Stores `new_task` under a newly generated id, running the create hooks,
+/// without checking for similar titles.
+///
+/// The task is written with `Storage::insert_new`, so an existing task is
+/// never overwritten: if another process took the id in the meantime, the
+/// next one is tried, and the `pre-create` hook will have seen the first.
+///
+/// # Arguments
+///
+/// * `tasks` - The stored tasks, used to generate the id and check the parent
+pub fn insert(store: &dyn Storage<Base>, tasks: &HashMap<String, Base>, new_task: NewTask) -> Result<ItemTypes, TaskError> {
+    if let Some(recurrence) = &new_task.recurrence {
+        Recurrence::parse(recurrence)?;
+    }
+    let title = normalize_title(&new_task.title);
+    let ids = IdScheme::from_env()?.generator(tasks.keys().map(String::as_str));
+    let id = ids.next_id();
+    if let Some(parent_id) = &new_task.parent_id
+        && !tasks.contains_key(parent_id)
+    {
+        return Err(TaskError::NotFound(parent_id.clone()));
+    }
+    let mut item = match new_task.status {
+        TaskStatus::PENDING => ItemTypes::Pending(Pending::new(&id, &title)),
+        TaskStatus::DONE => ItemTypes::Done(Done::new(&id, &title)),
+    };
+    item.base_mut().description = new_task.description.as_deref().and_then(normalize_description);
+    item.base_mut().priority = new_task.priority;
+    item.base_mut().due = new_task.due;
+    item.base_mut().parent_id = new_task.parent_id;
+    item.base_mut().recurrence = new_task.recurrence;
+    item.base_mut().previous_id = new_task.previous_id;
+    for tag in &new_task.tags {
+        item.base_mut().add_tag(tag);
+    }
+    hooks::run(store, HookStage::Pre, HookAction::Create, item.base())?;
+    // `tasks` may be out of date, so another process can have taken the id
+    // meanwhile (sequential ids make that likely); move on to the next one.
+    let mut attempts = 1;
+    loop {
+        match store.insert_new(&item.base().id.clone(), item.base()) {
+            Ok(()) => break,
+            Err(DalError::AlreadyExists(_)) if attempts < MAX_ID_ATTEMPTS => {
+                item.base_mut().id = ids.next_id();
+                attempts += 1;
+            }
+            Err(e) => return Err(e.into()),
         }
     }
+    hooks::run(store, HookStage::Post, HookAction::Create, item.base())?;
+    Ok(item)
 }
 
-pub fn create(title: &str, status: TaskStatus) -> Result<ItemTypes, String> {
-    let _ = save_one(&title.to_string(), &status)?;
-    match status {
-        TaskStatus::PENDING => {
-            Ok(ItemTypes::Pending(Pending::new(title)))
-        },
-        TaskStatus::DONE => {
-            Ok(ItemTypes::Done(Done::new(title)))
-        },
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{store_with, task};
+
+    fn new_task(title: &str) -> NewTask {
+        NewTask {
+            title: title.to_string(),
+            description: None,
+            status: TaskStatus::PENDING,
+            priority: Priority::default(),
+            due: None,
+            tags: Vec::new(),
+            parent_id: None,
+            recurrence: None,
+            previous_id: None,
+        }
+    }
+
+    #[test]
+    fn a_similar_title_is_returned_not_printed() {
+        let store = store_with("create-similar", vec![task("1", "Café au lait")]);
+        let created = create(&*store, new_task("  CAFE  au LAIT")).unwrap();
+        assert_eq!(created.similar.map(|similar| similar.id), Some("1".to_string()));
+        assert_eq!(created.item.base().title, "CAFE au LAIT");
+        let created = create(&*store, new_task("Tea")).unwrap();
+        assert!(created.similar.is_none());
+        assert_eq!(store.get_all().unwrap().len(), 3);
     }
-}
\ No newline at end of file
+}
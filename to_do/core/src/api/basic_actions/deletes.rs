@@ -0,0 +1,28 @@
+use dal::storage::Storage;
+
+use crate::structs::base::Base;
+use crate::errors::TaskError;
+use crate::hooks::{self, HookAction, HookStage};
+use super::creates::ItemTypes;
+
+/// Deletes a task by its id.
+///
+/// The `pre-delete` and `post-delete` hooks run around the removal; a
+/// failing pre hook aborts the deletion. Subtasks of the deleted task
+/// become top-level tasks.
+///
+/// # Returns
+///
+/// * `Ok(ItemTypes)` - The task that was deleted
+/// * `Err(TaskError::NotFound)` - If no task has this id
+pub fn delete(store: &dyn Storage<Base>, id: &str) -> Result<ItemTypes, TaskError> {
+    let base = store.get_one(id)?;
+    hooks::run(store, HookStage::Pre, HookAction::Delete, &base)?;
+    store.delete_one(id)?;
+    for mut child in store.get_all()?.into_values().filter(|task| task.parent_id.as_deref() == Some(id)) {
+        child.parent_id = None;
+        store.save_one(&child.id.clone(), &child)?;
+    }
+    hooks::run(store, HookStage::Post, HookAction::Delete, &base)?;
+    Ok(ItemTypes::from_base(base))
+}
@@ -0,0 +1,130 @@
+use chrono::{DateTime, Utc};
+use dal::storage::Storage;
+
+use crate::structs::base::Base;
+use crate::enums::{Priority, TaskStatus};
+use crate::errors::TaskError;
+use crate::normalize::{normalize_description, normalize_title};
+use crate::recurrence::Recurrence;
+use super::creates::ItemTypes;
+use super::transitions::{complete_task, reopen_task, Completion};
+
+/// A partial update to a task. Fields left as `None` are not changed.
+#[derive(Debug, Default)]
+pub struct TaskChanges {
+    pub title: Option<String>,
+    /// `Some` replaces the description; blank text clears it.
+    pub description: Option<String>,
+    /// Applied by `edit` through `complete` or `reopen`, never by `apply`.
+    pub status: Option<TaskStatus>,
+    pub priority: Option<Priority>,
+    /// `Some(None)` clears the due date.
+    pub due: Option<Option<DateTime<Utc>>>,
+    /// `Some(None)` stops the task from repeating.
+    pub recurrence: Option<Option<String>>,
+    pub add_tags: Vec<String>,
+    pub remove_tags: Vec<String>,
+}
+
+impl TaskChanges {
+    /// Returns true if no field would be changed.
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.description.is_none() && self.status.is_none() && self.priority.is_none() && self.due.is_none()
+            && self.recurrence.is_none()
+            && self.add_tags.is_empty() && self.remove_tags.is_empty()
+    }
+
+    /// Applies the changes to `base` in place, except the status.
+    pub fn apply(self, base: &mut Base) {
+        if let Some(title) = self.title {
+            base.title = normalize_title(&title);
+        }
+        if let Some(description) = self.description {
+            base.description = normalize_description(&description);
+        }
+        if let Some(priority) = self.priority {
+            base.priority = priority;
+        }
+        if let Some(due) = self.due {
+            base.due = due;
+        }
+        if let Some(recurrence) = self.recurrence {
+            base.recurrence = recurrence;
+        }
+        for tag in &self.remove_tags {
+            base.remove_tag(tag);
+        }
+        for tag in &self.add_tags {
+            base.add_tag(tag);
+        }
+    }
+}
+
+/// Applies `changes` to an existing task and persists it.
+///
+/// A new status goes through `complete_task` or `reopen_task`, so it's
+/// checked and runs the same hooks, and completing a recurring task creates
+/// its next instance. The other fields are saved in the same write, and
+/// only once those checks and the pre hook have passed.
+///
+/// # Returns
+///
+/// * `Ok(Completion)` - The updated task and, if it was completed and repeats, its next instance
+/// * `Err(TaskError::NoChanges)` - If `changes` doesn't change anything
+/// * `Err(TaskError::InvalidTransition)` - If the task already has the new status
+/// * `Err(TaskError::InvalidRecurrence)` - If the new recurrence can't be parsed
+/// * `Err(TaskError::NotFound)` - If no task has this id
+pub fn edit(store: &dyn Storage<Base>, id: &str, mut changes: TaskChanges) -> Result<Completion, TaskError> {
+    if changes.is_empty() {
+        return Err(TaskError::NoChanges);
+    }
+    if let Some(Some(recurrence)) = &changes.recurrence {
+        Recurrence::parse(recurrence)?;
+    }
+    let status = changes.status.take();
+    let mut base = store.get_one(id)?;
+    changes.apply(&mut base);
+    match status {
+        Some(TaskStatus::DONE) => complete_task(store, base),
+        Some(TaskStatus::PENDING) => Ok(Completion { task: reopen_task(store, base)?, next: None }),
+        None => {
+            store.save_one(id, &base)?;
+            Ok(Completion { task: ItemTypes::from_base(base), next: None })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{store_with, task};
+
+    fn renamed_and(status: TaskStatus) -> TaskChanges {
+        TaskChanges { title: Some("renamed".to_string()), status: Some(status), ..TaskChanges::default() }
+    }
+
+    #[test]
+    fn fields_and_status_are_saved_together() {
+        let store = store_with("edit-status", vec![task("1", "coding")]);
+        edit(&*store, "1", renamed_and(TaskStatus::DONE)).unwrap();
+        let stored = store.get_one("1").unwrap();
+        assert_eq!((stored.title.as_str(), stored.status), ("renamed", TaskStatus::DONE));
+    }
+
+    #[test]
+    fn a_rejected_status_change_saves_no_fields() {
+        let store = store_with("edit-status-rejected", vec![task("1", "coding")]);
+        assert!(matches!(edit(&*store, "1", renamed_and(TaskStatus::PENDING)), Err(TaskError::InvalidTransition { .. })));
+        let never = TaskChanges { recurrence: Some(Some("0 0 30 2 *".to_string())), ..renamed_and(TaskStatus::DONE) };
+        assert!(matches!(edit(&*store, "1", never), Err(TaskError::InvalidRecurrence(_))));
+        assert_eq!(store.get_one("1").unwrap().title, "coding");
+    }
+
+    #[test]
+    fn completing_through_edit_creates_the_next_instance() {
+        let store = store_with("edit-status-recurring", vec![task("1", "coding")]);
+        let changes = TaskChanges { recurrence: Some(Some("weekly".to_string())), ..renamed_and(TaskStatus::DONE) };
+        let next = edit(&*store, "1", changes).unwrap().next.expect("a next instance");
+        assert_eq!(next.base().title, "renamed");
+    }
+}
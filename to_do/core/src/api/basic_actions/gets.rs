@@ -0,0 +1,79 @@
+use chrono::Utc;
+use dal::errors::DalError;
+use dal::storage::Storage;
+
+use crate::structs::base::Base;
+use crate::dates::format_date;
+use crate::errors::TaskError;
+use super::creates::ItemTypes;
+
+/// Loads a single task by its id.
+///
+/// # Returns
+///
+/// * `Ok(ItemTypes)` - The task, wrapped in the variant matching its status
+/// * `Err(TaskError::NotFound)` - If no task has this id
+pub fn get(store: &dyn Storage<Base>, id: &str) -> Result<ItemTypes, TaskError> {
+    let base = store.get_one(id)?;
+    Ok(ItemTypes::from_base(base))
+}
+
+/// Loads a single task by its id, if there is one.
+///
+/// # Returns
+///
+/// * `Ok(Option<Base>)` - The task, or `None` if no task has this id
+/// * `Err(TaskError)` - If the store can't be read
+pub fn find(store: &dyn Storage<Base>, id: &str) -> Result<Option<Base>, TaskError> {
+    match store.get_one(id) {
+        Ok(base) => Ok(Some(base)),
+        Err(DalError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Renders a task as a labelled, one-field-per-line detail view, followed
+/// by its description, if any, after a blank line.
+pub fn render_detail(item: &ItemTypes) -> String {
+    let base = item.base();
+    let created = match base.created_at {
+        Some(created_at) => created_at.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        None => "-".to_string(),
+    };
+    let mut due = format_date(base.due);
+    if base.is_overdue(Utc::now()) {
+        due.push_str(" (overdue)");
+    }
+    let fields = [
+        ("ID", base.id.clone()),
+        ("Title", base.title.clone()),
+        ("Status", base.status.to_string()),
+        ("Priority", base.priority.to_string()),
+        ("Created", created),
+        ("Due", due),
+        ("Repeats", base.recurrence.clone().unwrap_or_else(|| "-".to_string())),
+        ("Parent", base.parent_id.clone().unwrap_or_else(|| "-".to_string())),
+        ("Tags", format_tags(&base.tags)),
+    ];
+    let mut detail: String = fields.iter()
+        .map(|(label, value)| format!("{:<9} {}\n", format!("{}:", label), value))
+        .collect();
+    if let Some(description) = &base.description {
+        detail.push_str(&format!("\n{}\n", description));
+    }
+    detail
+}
+
+/// Formats tags as a comma-separated list, using "-" when there are none.
+pub fn format_tags(tags: &[String]) -> String {
+    if tags.is_empty() {
+        "-".to_string()
+    } else {
+        tags.join(", ")
+    }
+}
+
+/// Renders a task as pretty-printed JSON, in the same shape it is stored.
+pub fn render_json(item: &ItemTypes) -> Result<String, TaskError> {
+    Ok(serde_json::to_string_pretty(item.base())?)
+}
@@ -0,0 +1,168 @@
+use chrono::Utc;
+use dal::storage::Storage;
+use unicode_width::UnicodeWidthStr;
+
+use crate::structs::base::Base;
+use crate::enums::TaskStatus;
+use crate::dates::format_date;
+use crate::errors::TaskError;
+use crate::normalize::normalize_tag;
+use super::gets::format_tags;
+use super::subtasks::tree_order;
+use crate::api::sorting::{sort, SortKey, SortOrder};
+use super::creates::ItemTypes;
+
+/// Which tasks `list` returns. Unset fields don't filter.
+#[derive(Debug, Default)]
+pub struct ListFilter {
+    /// Only tasks with this status.
+    pub status: Option<TaskStatus>,
+    /// Only tasks carrying this tag; matched after normalizing it.
+    pub tag: Option<String>,
+}
+
+/// A page of `list` output: `limit` tasks, starting at 1-based `page`.
+#[derive(Debug, Clone, Copy)]
+pub struct Pagination {
+    pub limit: usize,
+    pub page: usize,
+}
+
+impl Pagination {
+    fn offset(&self) -> usize {
+        self.page.saturating_sub(1).saturating_mul(self.limit)
+    }
+}
+
+/// Loads the stored tasks matching `filter`, ordered by `order`.
+///
+/// Tag filtering is done by the storage backend (`Storage::get_by_tag`).
+pub fn list(store: &dyn Storage<Base>, filter: &ListFilter, order: &SortOrder) -> Result<Vec<ItemTypes>, TaskError> {
+    let tasks = match &filter.tag {
+        Some(tag) => store.get_by_tag(&normalize_tag(tag))?,
+        None => store.get_all()?,
+    };
+    let mut tasks: Vec<Base> = tasks
+        .into_values()
+        .filter(|task| filter.status.as_ref().is_none_or(|status| &task.status == status))
+        .collect();
+    sort(&mut tasks, order);
+    Ok(tasks.into_iter().map(ItemTypes::from_base).collect())
+}
+
+/// Loads one page of the tasks `list` would return.
+///
+/// Unfiltered listings in ascending id order are paged by the storage
+/// backend (`Storage::get_page`), so only the page is loaded; otherwise all
+/// matching tasks are loaded and sorted before the page is cut out.
+///
+/// # Returns
+///
+/// * `Ok((Vec<ItemTypes>, usize))` - The tasks on the page and how many tasks match in total
+/// * `Err(TaskError)` - If the store can't be read
+pub fn list_page(
+    store: &dyn Storage<Base>,
+    filter: &ListFilter,
+    order: &SortOrder,
+    pagination: &Pagination,
+) -> Result<(Vec<ItemTypes>, usize), TaskError> {
+    let unfiltered = filter.status.is_none() && filter.tag.is_none();
+    if unfiltered && order.key == SortKey::Id && !order.reverse {
+        let page = store.get_page(pagination.offset(), pagination.limit)?;
+        let items = page.items.into_iter().map(|(_, base)| ItemTypes::from_base(base)).collect();
+        return Ok((items, page.total));
+    }
+    let items = list(store, filter, order)?;
+    let total = items.len();
+    let items = items.into_iter().skip(pagination.offset()).take(pagination.limit).collect();
+    Ok((items, total))
+}
+
+/// Renders tasks as an aligned table with ID, TITLE, STATUS, PRIORITY, CREATED, DUE and TAGS columns.
+///
+/// # Arguments
+///
+/// * `color` - Highlight overdue tasks in red with ANSI escapes
+pub fn render_table(items: &[ItemTypes], color: bool) -> String {
+    render(items.iter().map(|item| (item, 0)), color)
+}
+
+/// Renders tasks like `render_table`, but with subtasks below their parent
+/// and their titles indented by depth.
+pub fn render_tree(items: &[ItemTypes], color: bool) -> String {
+    render(tree_order(items).into_iter(), color)
+}
+
+fn render<'a>(items: impl Iterator<Item = (&'a ItemTypes, usize)>, color: bool) -> String {
+    let now = Utc::now();
+    let mut rows = vec![[
+        "ID".to_string(),
+        "TITLE".to_string(),
+        "STATUS".to_string(),
+        "PRIORITY".to_string(),
+        "CREATED".to_string(),
+        "DUE".to_string(),
+        "TAGS".to_string(),
+    ]];
+    // Whether each row is overdue; the header never is.
+    let mut overdue = vec![false];
+    for (item, depth) in items {
+        let base = item.base();
+        rows.push([
+            base.id.clone(),
+            format!("{}{}", "  ".repeat(depth), base.title),
+            base.status.to_string(),
+            base.priority.to_string(),
+            format_date(base.created_at),
+            format_date(base.due),
+            format_tags(&base.tags),
+        ]);
+        overdue.push(base.is_overdue(now));
+    }
+    let mut widths = [0; 7];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.width());
+        }
+    }
+    let mut table = String::new();
+    for (row, overdue) in rows.iter().zip(overdue) {
+        let cells: Vec<String> = row.iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{}{}", cell, " ".repeat(width - cell.width())))
+            .collect();
+        let line = cells.join("  ");
+        // Colour after padding so the escapes don't count towards the widths.
+        if color && overdue {
+            table.push_str(&format!("\x1b[31m{}\x1b[0m", line.trim_end()));
+        } else {
+            table.push_str(line.trim_end());
+        }
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{store_with, task};
+
+    fn page_of(store: &dyn Storage<Base>, key: SortKey, limit: usize, page: usize) -> (Vec<String>, usize) {
+        let order = SortOrder { key, reverse: false };
+        let (items, total) = list_page(store, &ListFilter::default(), &order, &Pagination { limit, page }).unwrap();
+        (items.iter().map(|item| item.base().id.clone()).collect(), total)
+    }
+
+    #[test]
+    fn the_last_page_can_be_partial_and_later_ones_are_empty() {
+        let store = store_with("list-pages", ["1", "2", "3", "4", "5"].map(|id| task(id, &format!("Task {}", id))).to_vec());
+        // Paged by the backend, and sorted in memory.
+        for key in [SortKey::Id, SortKey::Title] {
+            assert_eq!(page_of(&*store, key, 2, 1), (vec!["1".to_string(), "2".to_string()], 5));
+            assert_eq!(page_of(&*store, key, 2, 3), (vec!["5".to_string()], 5));
+            assert_eq!(page_of(&*store, key, 2, 4), (Vec::new(), 5));
+            assert_eq!(page_of(&*store, key, 2, usize::MAX), (Vec::new(), 5));
+        }
+    }
+}
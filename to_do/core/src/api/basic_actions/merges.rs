@@ -0,0 +1,114 @@
+use dal::storage::Storage;
+
+use crate::structs::base::Base;
+use crate::errors::TaskError;
+use super::creates::ItemTypes;
+use super::deletes::delete;
+use super::subtasks::descendants;
+
+/// Merges `duplicate_ids` into `keep_id` and deletes the duplicates.
+///
+/// The kept task gains the duplicates' tags and any description it doesn't
+/// already contain, the highest priority and the earliest due date among
+/// them, and their recurrence if it has none. Subtasks of the duplicates
+/// become subtasks of the kept task. Its title and status stay as they are.
+///
+/// The duplicates are removed with `delete`, so the delete hooks run for
+/// each of them.
+///
+/// # Returns
+///
+/// * `Ok(ItemTypes)` - The kept task, after the merge
+/// * `Err(TaskError::NotFound)` - If any of the tasks doesn't exist
+/// * `Err(TaskError::InvalidMerge)` - If there is nothing to merge, or the kept task is among the duplicates or one of their subtasks
+pub fn merge(store: &dyn Storage<Base>, keep_id: &str, duplicate_ids: &[String]) -> Result<ItemTypes, TaskError> {
+    if duplicate_ids.is_empty() {
+        return Err(TaskError::InvalidMerge("Nothing to merge; name at least one duplicate".to_string()));
+    }
+    let tasks = store.get_all()?;
+    let mut keep = tasks.get(keep_id).cloned().ok_or_else(|| TaskError::NotFound(keep_id.to_string()))?;
+    let mut duplicates = Vec::new();
+    for id in duplicate_ids {
+        let duplicate = tasks.get(id).ok_or_else(|| TaskError::NotFound(id.clone()))?;
+        if id == keep_id || descendants(&tasks, id).iter().any(|below| below == keep_id) {
+            return Err(TaskError::InvalidMerge(format!(
+                "{} can't be merged into {}, which is itself or one of its subtasks", id, keep_id
+            )));
+        }
+        if !duplicates.iter().any(|seen: &&Base| seen.id == *id) {
+            duplicates.push(duplicate);
+        }
+    }
+    for duplicate in &duplicates {
+        if let Some(description) = &duplicate.description {
+            keep.description = match keep.description.take() {
+                Some(kept) if kept.contains(description.as_str()) => Some(kept),
+                Some(kept) => Some(format!("{}\n\n{}", kept, description)),
+                None => Some(description.clone()),
+            };
+        }
+        for tag in &duplicate.tags {
+            keep.add_tag(tag);
+        }
+        keep.priority = keep.priority.max(duplicate.priority);
+        keep.due = match (keep.due, duplicate.due) {
+            (Some(kept), Some(other)) => Some(kept.min(other)),
+            (kept, other) => kept.or(other),
+        };
+        if keep.recurrence.is_none() {
+            keep.recurrence = duplicate.recurrence.clone();
+        }
+    }
+    store.save_one(keep_id, &keep)?;
+    for mut child in tasks.values().filter(|task| task.parent_id.as_ref().is_some_and(|id| duplicate_ids.contains(id))).cloned() {
+        child.parent_id = Some(keep_id.to_string());
+        store.save_one(&child.id.clone(), &child)?;
+    }
+    for duplicate in &duplicates {
+        delete(store, &duplicate.id)?;
+    }
+    Ok(ItemTypes::from_base(keep))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::Priority;
+    use crate::testing::{store_with, task};
+
+    #[test]
+    fn duplicates_are_folded_into_the_kept_task() {
+        let keep = Base { tags: vec!["home".to_string()], ..task("1", "Buy milk") };
+        let duplicate = Base {
+            tags: vec!["home".to_string(), "shop".to_string()],
+            priority: Priority::High,
+            description: Some("Oat milk".to_string()),
+            ..task("2", "buy milk")
+        };
+        let child = Base { parent_id: Some("2".to_string()), ..task("3", "Find a bag") };
+        let store = store_with("merge", vec![keep, duplicate, child, task("4", "Unrelated")]);
+
+        let merged = merge(&*store, "1", &["2".to_string()]).unwrap();
+        assert_eq!(merged.base().tags, ["home", "shop"]);
+        assert_eq!(merged.base().priority, Priority::High);
+        assert_eq!(merged.base().description.as_deref(), Some("Oat milk"));
+        assert_eq!(merged.base().title, "Buy milk");
+
+        let tasks = store.get_all().unwrap();
+        assert!(!tasks.contains_key("2"));
+        assert_eq!(tasks["3"].parent_id.as_deref(), Some("1"));
+        assert_eq!(tasks["1"].tags, ["home", "shop"]);
+        assert_eq!(tasks["4"].parent_id, None);
+    }
+
+    #[test]
+    fn merging_into_itself_or_a_subtask_is_rejected() {
+        let child = Base { parent_id: Some("2".to_string()), ..task("3", "Find a bag") };
+        let store = store_with("merge-self", vec![task("1", "Buy milk"), task("2", "buy milk"), child]);
+        assert!(matches!(merge(&*store, "1", &["1".to_string()]), Err(TaskError::InvalidMerge(_))));
+        assert!(matches!(merge(&*store, "3", &["2".to_string()]), Err(TaskError::InvalidMerge(_))));
+        assert!(matches!(merge(&*store, "1", &[]), Err(TaskError::InvalidMerge(_))));
+        assert!(matches!(merge(&*store, "1", &["7".to_string()]), Err(TaskError::NotFound(id)) if id == "7"));
+        assert_eq!(store.get_all().unwrap().len(), 3);
+    }
+}
@@ -1 +1,8 @@
-pub mod creates;
\ No newline at end of file
+pub mod creates;
+pub mod deletes;
+pub mod edits;
+pub mod gets;
+pub mod lists;
+pub mod merges;
+pub mod subtasks;
+pub mod transitions;
\ No newline at end of file
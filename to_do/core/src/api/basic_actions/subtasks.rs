@@ -0,0 +1,95 @@
+use dal::storage::Storage;
+use std::collections::HashMap;
+
+use crate::structs::base::Base;
+use crate::errors::TaskError;
+use super::creates::ItemTypes;
+
+/// Makes `child_id` a subtask of `parent_id`, replacing any previous parent.
+///
+/// # Returns
+///
+/// * `Ok(ItemTypes)` - The updated child
+/// * `Err(TaskError::NotFound)` - If either task doesn't exist
+/// * `Err(TaskError::InvalidParent)` - If the parent is the child itself or one of its subtasks
+pub fn attach(store: &dyn Storage<Base>, child_id: &str, parent_id: &str) -> Result<ItemTypes, TaskError> {
+    let tasks = store.get_all()?;
+    let mut child = tasks.get(child_id).cloned().ok_or_else(|| TaskError::NotFound(child_id.to_string()))?;
+    check_parent(&tasks, child_id, parent_id)?;
+    child.parent_id = Some(parent_id.to_string());
+    store.save_one(child_id, &child)?;
+    Ok(ItemTypes::from_base(child))
+}
+
+/// Turns a subtask back into a top-level task.
+///
+/// # Returns
+///
+/// * `Ok(ItemTypes)` - The updated task
+/// * `Err(TaskError::InvalidParent)` - If the task has no parent
+/// * `Err(TaskError::NotFound)` - If no task has this id
+pub fn detach(store: &dyn Storage<Base>, id: &str) -> Result<ItemTypes, TaskError> {
+    let mut base = store.get_one(id)?;
+    if base.parent_id.take().is_none() {
+        return Err(TaskError::InvalidParent(format!("{} is not a subtask", id)));
+    }
+    store.save_one(id, &base)?;
+    Ok(ItemTypes::from_base(base))
+}
+
+/// Checks that `parent_id` exists and that giving it `child_id` as a
+/// subtask wouldn't create a cycle.
+pub fn check_parent(tasks: &HashMap<String, Base>, child_id: &str, parent_id: &str) -> Result<(), TaskError> {
+    let mut ancestor = tasks.get(parent_id).ok_or_else(|| TaskError::NotFound(parent_id.to_string()))?;
+    loop {
+        if ancestor.id == child_id {
+            return Err(TaskError::InvalidParent(format!(
+                "{} can't be a subtask of {}, which is itself or one of its subtasks", child_id, parent_id
+            )));
+        }
+        match ancestor.parent_id.as_ref().and_then(|id| tasks.get(id)) {
+            Some(next) => ancestor = next,
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Returns the ids of every subtask below `id`, children before grandchildren.
+pub fn descendants(tasks: &HashMap<String, Base>, id: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut queue = vec![id.to_string()];
+    while let Some(parent) = queue.pop() {
+        for task in tasks.values() {
+            if task.parent_id.as_deref() == Some(parent.as_str()) && !found.contains(&task.id) {
+                found.push(task.id.clone());
+                queue.push(task.id.clone());
+            }
+        }
+    }
+    found
+}
+
+/// Orders `items` depth first, each task followed by its subtasks, keeping
+/// the given order among siblings. Tasks whose parent isn't among `items`
+/// are shown at the top level.
+///
+/// # Returns
+///
+/// The items with their depth in the tree, 0 for top-level tasks.
+pub fn tree_order(items: &[ItemTypes]) -> Vec<(&ItemTypes, usize)> {
+    let present: Vec<&str> = items.iter().map(|item| item.base().id.as_str()).collect();
+    let is_root = |item: &ItemTypes| {
+        item.base().parent_id.as_deref().is_none_or(|parent| !present.contains(&parent))
+    };
+    let mut ordered = Vec::with_capacity(items.len());
+    let mut stack: Vec<(&ItemTypes, usize)> = items.iter().filter(|item| is_root(item)).rev().map(|item| (item, 0)).collect();
+    while let Some((item, depth)) = stack.pop() {
+        ordered.push((item, depth));
+        let id = item.base().id.as_str();
+        stack.extend(items.iter()
+            .filter(|child| child.base().parent_id.as_deref() == Some(id))
+            .rev()
+            .map(|child| (child, depth + 1)));
+    }
+    ordered
+}
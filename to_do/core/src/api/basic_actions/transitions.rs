@@ -0,0 +1,220 @@
+use chrono::{DateTime, Utc};
+use dal::storage::Storage;
+use std::fmt;
+
+use crate::structs::{
+    base::Base,
+    done::Done,
+    pending::Pending,
+};
+use crate::enums::TaskStatus;
+use crate::errors::TaskError;
+use crate::hooks::{self, HookAction, HookStage};
+use crate::dates::format_date;
+use crate::recurrence::Recurrence;
+use super::creates::{insert, ItemTypes, NewTask};
+use super::subtasks::descendants;
+
+/// A completed task and, for a recurring one, the next instance created for it.
+pub struct Completion {
+    pub task: ItemTypes,
+    pub next: Option<ItemTypes>,
+}
+
+impl fmt::Display for Completion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.task)?;
+        if let Some(next) = &self.next {
+            write!(f, "\nNext: {} (due {})", next, format_date(next.base().due))?;
+        }
+        Ok(())
+    }
+}
+
+/// Marks a pending task as done.
+///
+/// The `pre-done` and `post-done` hooks run around the write; a failing pre
+/// hook aborts the transition. Completing a recurring task creates its next
+/// instance, a copy due at the next occurrence (see `Recurrence::next_after`),
+/// unless it has one already, e.g. because it was completed and reopened.
+///
+/// # Returns
+///
+/// * `Ok(Completion)` - The completed task and the next instance, if one was created
+/// * `Err(TaskError::InvalidTransition)` - If the task is already done
+/// * `Err(TaskError::InvalidRecurrence)` - If the stored recurrence can't be parsed or never occurs again
+/// * `Err(TaskError::NotFound)` - If no task has this id
+pub fn complete(store: &dyn Storage<Base>, id: &str) -> Result<Completion, TaskError> {
+    complete_task(store, store.get_one(id)?)
+}
+
+/// Like `complete`, for a stored task that may have unsaved changes; they
+/// are saved together with the new status. Nothing is written if a check
+/// or the pre hook fails.
+pub fn complete_task(store: &dyn Storage<Base>, mut base: Base) -> Result<Completion, TaskError> {
+    if base.status == TaskStatus::DONE {
+        return Err(invalid(&base, TaskStatus::DONE));
+    }
+    // Work out the next occurrence first, so a recurrence that can't be
+    // parsed or never occurs again is reported before anything is written.
+    let next_due = match base.recurrence.as_deref() {
+        Some(text) => Some(Recurrence::parse(text)?
+            .next_after(base.due, Utc::now())
+            .ok_or_else(|| TaskError::InvalidRecurrence(text.to_string()))?),
+        None => None,
+    };
+    base.status = TaskStatus::DONE;
+    hooks::run(store, HookStage::Pre, HookAction::Done, &base)?;
+    // The next instance comes first: if saving the task fails after it, the
+    // task is still pending and `done` creates nothing new the second time.
+    let next = match next_due {
+        Some(due) => create_next(store, &base, due)?,
+        None => None,
+    };
+    store.save_one(&base.id, &base)?;
+    hooks::run(store, HookStage::Post, HookAction::Done, &base)?;
+    Ok(Completion { task: ItemTypes::Done(Done { super_struct: base }), next })
+}
+
+/// Creates the next instance, due at `due`, of a recurring task that is
+/// being completed, unless one was created for it before.
+fn create_next(store: &dyn Storage<Base>, done: &Base, due: DateTime<Utc>) -> Result<Option<ItemTypes>, TaskError> {
+    let tasks = store.get_all()?;
+    if tasks.values().any(|task| task.previous_id.as_deref() == Some(done.id.as_str())) {
+        return Ok(None);
+    }
+    let next = NewTask {
+        title: done.title.clone(),
+        description: done.description.clone(),
+        status: TaskStatus::PENDING,
+        priority: done.priority,
+        due: Some(due),
+        tags: done.tags.clone(),
+        parent_id: done.parent_id.clone(),
+        recurrence: done.recurrence.clone(),
+        previous_id: Some(done.id.clone()),
+    };
+    insert(store, &tasks, next).map(Some)
+}
+
+/// Marks a pending task as done together with all of its pending subtasks,
+/// deepest first, so no completed task is left with open subtasks.
+///
+/// # Returns
+///
+/// * `Ok(Vec<Completion>)` - The completed tasks, ending with `id` itself
+/// * `Err(TaskError::InvalidTransition)` - If the task itself is already done
+/// * `Err(TaskError::NotFound)` - If no task has this id
+pub fn complete_cascade(store: &dyn Storage<Base>, id: &str) -> Result<Vec<Completion>, TaskError> {
+    let tasks = store.get_all()?;
+    match tasks.get(id) {
+        None => return Err(TaskError::NotFound(id.to_string())),
+        Some(base) if base.status == TaskStatus::DONE => return Err(invalid(base, TaskStatus::DONE)),
+        Some(_) => {}
+    }
+    let mut completed = Vec::new();
+    for child in descendants(&tasks, id).iter().rev() {
+        if tasks[child].status == TaskStatus::PENDING {
+            completed.push(complete(store, child)?);
+        }
+    }
+    completed.push(complete(store, id)?);
+    Ok(completed)
+}
+
+/// Moves a done task back to pending.
+///
+/// The `pre-reopen` and `post-reopen` hooks run around the write; a failing
+/// pre hook aborts the transition. The next instance of a recurring task
+/// stays, so completing it again doesn't create another one.
+///
+/// # Returns
+///
+/// * `Ok(ItemTypes::Pending)` - The reopened task
+/// * `Err(TaskError::InvalidTransition)` - If the task is already pending
+/// * `Err(TaskError::NotFound)` - If no task has this id
+pub fn reopen(store: &dyn Storage<Base>, id: &str) -> Result<ItemTypes, TaskError> {
+    reopen_task(store, store.get_one(id)?)
+}
+
+/// Like `reopen`, for a stored task that may have unsaved changes; they
+/// are saved together with the new status.
+pub fn reopen_task(store: &dyn Storage<Base>, mut base: Base) -> Result<ItemTypes, TaskError> {
+    if base.status == TaskStatus::PENDING {
+        return Err(invalid(&base, TaskStatus::PENDING));
+    }
+    base.status = TaskStatus::PENDING;
+    hooks::run(store, HookStage::Pre, HookAction::Reopen, &base)?;
+    store.save_one(&base.id, &base)?;
+    hooks::run(store, HookStage::Post, HookAction::Reopen, &base)?;
+    Ok(ItemTypes::Pending(Pending { super_struct: base }))
+}
+
+/// The error for moving `base` to the status `to`, which it already has.
+fn invalid(base: &Base, to: TaskStatus) -> TaskError {
+    TaskError::InvalidTransition { id: base.id.clone(), from: base.status.clone(), to }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{store_with, task};
+
+    fn recurring(id: &str, recurrence: &str) -> Base {
+        Base { recurrence: Some(recurrence.to_string()), ..task(id, "water the plants") }
+    }
+
+    fn pending(store: &dyn Storage<Base>) -> Vec<Base> {
+        store.get_all().unwrap().into_values().filter(|task| task.status == TaskStatus::PENDING).collect()
+    }
+
+    #[test]
+    fn completing_a_recurring_task_creates_the_next_instance() {
+        let store = store_with("complete-recurring", vec![recurring("1", "daily")]);
+        let completion = complete(&*store, "1").unwrap();
+        let next = completion.next.expect("a next instance");
+        assert_eq!(next.base().previous_id.as_deref(), Some("1"));
+        assert_eq!(next.base().recurrence.as_deref(), Some("daily"));
+        assert_eq!(store.get_one("1").unwrap().status, TaskStatus::DONE);
+        assert_eq!(pending(&*store).len(), 1);
+    }
+
+    #[test]
+    fn completing_again_after_reopening_creates_no_second_instance() {
+        let store = store_with("complete-reopen-complete", vec![recurring("1", "daily")]);
+        complete(&*store, "1").unwrap();
+        reopen(&*store, "1").unwrap();
+        assert!(complete(&*store, "1").unwrap().next.is_none());
+        assert_eq!(store.get_all().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn a_recurrence_that_never_occurs_writes_nothing() {
+        let store = store_with("complete-never", vec![recurring("1", "0 0 30 2 *")]);
+        assert!(matches!(complete(&*store, "1"), Err(TaskError::InvalidRecurrence(_))));
+        assert_eq!(pending(&*store).len(), 1);
+        assert_eq!(store.get_all().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn transitions_to_the_current_status_are_rejected() {
+        let store = store_with("transitions-invalid", vec![task("1", "coding")]);
+        assert!(matches!(reopen(&*store, "1"), Err(TaskError::InvalidTransition { to: TaskStatus::PENDING, .. })));
+        complete(&*store, "1").unwrap();
+        assert!(matches!(complete(&*store, "1"), Err(TaskError::InvalidTransition { to: TaskStatus::DONE, .. })));
+        assert!(matches!(complete(&*store, "2"), Err(TaskError::NotFound(id)) if id == "2"));
+    }
+
+    #[test]
+    fn cascade_completes_the_open_subtasks() {
+        let child = Base { parent_id: Some("1".to_string()), ..task("2", "child") };
+        let grandchild = Base { parent_id: Some("2".to_string()), ..task("3", "grandchild") };
+        let store = store_with("complete-cascade", vec![task("1", "parent"), child, grandchild]);
+        let completed: Vec<String> = complete_cascade(&*store, "1").unwrap()
+            .iter()
+            .map(|completion| completion.task.base().id.clone())
+            .collect();
+        assert_eq!(completed, vec!["3", "2", "1"]);
+        assert!(pending(&*store).is_empty());
+    }
+}
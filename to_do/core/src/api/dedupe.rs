@@ -0,0 +1,129 @@
+use dal::storage::Storage;
+use std::collections::HashMap;
+
+use crate::structs::base::Base;
+use crate::enums::TaskStatus;
+use crate::errors::TaskError;
+use crate::normalize::{jaccard, match_key, trigrams};
+
+/// Titles at least this similar (trigram Jaccard, 0..=1) are taken for
+/// duplicates even when their match keys differ, e.g. because of a typo.
+const DUPLICATE_SIMILARITY: f64 = 0.75;
+
+/// Open tasks that look like the same task entered more than once.
+#[derive(Debug, Clone)]
+pub struct Cluster {
+    /// The oldest task of the cluster, the one the others are merged into.
+    pub keep: Base,
+    /// The other tasks, oldest first.
+    pub duplicates: Vec<Base>,
+}
+
+impl Cluster {
+    /// The ids of the duplicates, as `merge` takes them.
+    pub fn duplicate_ids(&self) -> Vec<String> {
+        self.duplicates.iter().map(|task| task.id.clone()).collect()
+    }
+}
+
+/// Groups pending tasks likely to be duplicates of each other.
+///
+/// Two tasks are alike when they have the same parent (so the same subtask
+/// under two different tasks isn't one) and their titles share a match key
+/// or are very similar. Tasks alike to a common third one end up in the
+/// same cluster.
+///
+/// # Returns
+///
+/// * `Ok(Vec<Cluster>)` - Clusters of two or more tasks, the one with the oldest task first
+/// * `Err(TaskError)` - If the store can't be read
+pub fn find_duplicates(store: &dyn Storage<Base>) -> Result<Vec<Cluster>, TaskError> {
+    let mut open: Vec<Base> = store.get_all()?
+        .into_values()
+        .filter(|task| task.status == TaskStatus::PENDING)
+        .collect();
+    open.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+    let keys: Vec<(String, _)> = open.iter().map(|task| (match_key(&task.title), trigrams(&task.title))).collect();
+    // Each task points at an earlier task of its cluster; roots stand for the cluster.
+    let mut root: Vec<usize> = (0..open.len()).collect();
+    let find = |root: &Vec<usize>, mut i: usize| {
+        while root[i] != i {
+            i = root[i];
+        }
+        i
+    };
+    for i in 0..open.len() {
+        for j in 0..i {
+            let alike = open[i].parent_id == open[j].parent_id
+                && (keys[i].0 == keys[j].0 || jaccard(&keys[i].1, &keys[j].1) >= DUPLICATE_SIMILARITY);
+            if alike {
+                let (a, b) = (find(&root, i), find(&root, j));
+                root[a.max(b)] = a.min(b);
+            }
+        }
+    }
+    let mut members: HashMap<usize, Vec<Base>> = HashMap::new();
+    for (i, task) in open.iter().enumerate() {
+        members.entry(find(&root, i)).or_default().push(task.clone());
+    }
+    let mut clusters: Vec<(usize, Cluster)> = members.into_iter()
+        .filter(|(_, tasks)| tasks.len() > 1)
+        .map(|(first, mut tasks)| {
+            let keep = tasks.remove(0);
+            (first, Cluster { keep, duplicates: tasks })
+        })
+        .collect();
+    clusters.sort_by_key(|(first, _)| *first);
+    Ok(clusters.into_iter().map(|(_, cluster)| cluster).collect())
+}
+
+/// Renders a cluster: the task to keep, then each duplicate indented below it.
+pub fn render_cluster(cluster: &Cluster) -> String {
+    let mut out = format!("{} {}\n", cluster.keep.id, cluster.keep.title);
+    for task in &cluster.duplicates {
+        out.push_str(&format!("  duplicate {} {}\n", task.id, task.title));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{store_with, task};
+
+    /// A task created at the same (unknown) time as the others, so the
+    /// clusters are ordered by id.
+    fn open(id: &str, title: &str) -> Base {
+        Base { created_at: None, ..task(id, title) }
+    }
+
+    fn ids(cluster: &Cluster) -> (String, Vec<String>) {
+        (cluster.keep.id.clone(), cluster.duplicate_ids())
+    }
+
+    #[test]
+    fn titles_differing_in_case_accents_or_a_typo_are_clustered() {
+        let store = store_with("dedupe-variants", vec![
+            open("1", "Café au lait"),
+            open("2", "CAFE AU LAIT"),
+            open("3", "cafe  au lait"),
+            open("4", "Renew the passport at the town hall"),
+            open("5", "Renew the pasport at the town hall"),
+            open("6", "Call mum"),
+        ]);
+        let clusters = find_duplicates(&*store).unwrap();
+        let clusters: Vec<_> = clusters.iter().map(ids).collect();
+        assert_eq!(clusters, vec![
+            ("1".to_string(), vec!["2".to_string(), "3".to_string()]),
+            ("4".to_string(), vec!["5".to_string()]),
+        ]);
+    }
+
+    #[test]
+    fn done_tasks_and_other_parents_are_left_out() {
+        let done = Base { status: TaskStatus::DONE, ..open("2", "Buy milk") };
+        let subtask = Base { parent_id: Some("9".to_string()), ..open("3", "buy milk") };
+        let store = store_with("dedupe-excluded", vec![open("1", "Buy milk"), done, subtask, open("9", "Groceries")]);
+        assert!(find_duplicates(&*store).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,178 @@
+use chrono::{DateTime, Utc};
+use dal::oplog::{OpLog, Stack};
+use dal::storage::Storage;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use crate::structs::base::{Base, HistoryEntry};
+use crate::errors::TaskError;
+use super::audit::{current_user, describe_changes};
+use super::basic_actions::gets::find;
+
+/// One task as it was before and after an operation; `None` means it
+/// didn't exist.
+///
+/// When the task exists on both sides its `history` is left out of both, so
+/// moving between them never rewrites the audit trail. A task that exists on
+/// one side only keeps it there, since nothing is stored to overwrite.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Change {
+    pub id: String,
+    pub before: Option<Base>,
+    pub after: Option<Base>,
+}
+
+/// A command that changed tasks, with everything needed to take it back.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Operation {
+    /// The command as it was typed, e.g. `delete 01a1... --force`.
+    pub label: String,
+    pub at: DateTime<Utc>,
+    pub changes: Vec<Change>,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({} task(s))", self.label, self.changes.len())
+    }
+}
+
+/// Compares the tasks a command wrote before and after it ran, ignoring
+/// their history.
+///
+/// # Arguments
+///
+/// * `before`, `after` - The tasks the command wrote, keyed by id; `None` where a task didn't exist
+///
+/// # Returns
+///
+/// * `Some(Operation)` - The tasks that were created, changed or deleted
+/// * `None` - If nothing changed
+pub fn diff(label: &str, before: &HashMap<String, Option<Base>>, after: &HashMap<String, Option<Base>>) -> Option<Operation> {
+    let ids: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    let state = |tasks: &HashMap<String, Option<Base>>, id: &str| tasks.get(id).cloned().flatten();
+    let changes: Vec<Change> = ids.into_iter()
+        .filter(|id| !same(&state(before, id), &state(after, id)))
+        .map(|id| match (state(before, id), state(after, id)) {
+            (Some(before), Some(after)) => Change {
+                id: id.clone(),
+                before: Some(without_history(&before)),
+                after: Some(without_history(&after)),
+            },
+            (before, after) => Change { id: id.clone(), before, after },
+        })
+        .collect();
+    if changes.is_empty() {
+        return None;
+    }
+    Some(Operation { label: label.to_string(), at: Utc::now(), changes })
+}
+
+/// Records the changes a command made, if any, so it can be undone.
+pub fn record(log: &OpLog, label: &str, before: &HashMap<String, Option<Base>>, after: &HashMap<String, Option<Base>>) -> Result<(), TaskError> {
+    if let Some(operation) = diff(label, before, after) {
+        log.record(&operation)?;
+    }
+    Ok(())
+}
+
+/// Restores the tasks changed by the most recent operation and moves it to
+/// the redo stack. Hooks don't run.
+///
+/// # Returns
+///
+/// * `Ok(Operation)` - The operation that was undone
+/// * `Err(TaskError::History)` - If there is nothing to undo, or a task it changed was changed again since
+pub fn undo(store: &dyn Storage<Base>, log: &OpLog) -> Result<Operation, TaskError> {
+    let operation: Operation = log.pop(Stack::Undo)?
+        .ok_or_else(|| TaskError::History("Nothing to undo".to_string()))?;
+    match replay(store, &operation, false) {
+        Ok(()) => log.push(Stack::Redo, &operation)?,
+        Err(e) => {
+            log.push(Stack::Undo, &operation)?;
+            return Err(e);
+        }
+    }
+    Ok(operation)
+}
+
+/// Applies the most recently undone operation again and moves it back to
+/// the undo stack. Hooks don't run.
+///
+/// # Returns
+///
+/// * `Ok(Operation)` - The operation that was redone
+/// * `Err(TaskError::History)` - If there is nothing to redo, or a task it changes was changed since the undo
+pub fn redo(store: &dyn Storage<Base>, log: &OpLog) -> Result<Operation, TaskError> {
+    let operation: Operation = log.pop(Stack::Redo)?
+        .ok_or_else(|| TaskError::History("Nothing to redo".to_string()))?;
+    match replay(store, &operation, true) {
+        Ok(()) => log.push(Stack::Undo, &operation)?,
+        Err(e) => {
+            log.push(Stack::Redo, &operation)?;
+            return Err(e);
+        }
+    }
+    Ok(operation)
+}
+
+/// Moves every changed task to its state after (`forward`) or before the
+/// operation, after checking that all of them are still in the opposite
+/// state, so a later change is never silently overwritten.
+///
+/// A restored task keeps the history it has now, and gets an entry saying
+/// what the undo or redo changed.
+fn replay(store: &dyn Storage<Base>, operation: &Operation, forward: bool) -> Result<(), TaskError> {
+    let mut current = Vec::new();
+    for change in &operation.changes {
+        let expected = if forward { &change.before } else { &change.after };
+        let task = find(store, &change.id)?;
+        if !same(&task, expected) {
+            return Err(TaskError::History(format!(
+                "Task {} was changed after `{}`; it can't be {} safely",
+                change.id, operation.label, if forward { "redone" } else { "undone" },
+            )));
+        }
+        current.push(task);
+    }
+    let by = current_user();
+    let at = Utc::now();
+    let verb = if forward { "redo" } else { "undo" };
+    for (change, current) in operation.changes.iter().zip(current) {
+        let target = if forward { &change.after } else { &change.before };
+        match target {
+            Some(target) => {
+                let mut task = target.clone();
+                let changes = match current {
+                    Some(current) => {
+                        let changes = describe_changes(Some(&current), &task);
+                        task.history = current.history;
+                        changes
+                    }
+                    None => vec!["restored".to_string()],
+                };
+                task.history.extend(changes.into_iter().map(|change| HistoryEntry {
+                    at,
+                    by: by.clone(),
+                    change: format!("{} `{}`: {}", verb, operation.label, change),
+                }));
+                store.save_one(&change.id, &task)?;
+            }
+            None => store.delete_one(&change.id)?,
+        }
+    }
+    Ok(())
+}
+
+/// Whether two states of a task are the same apart from their history.
+fn same(a: &Option<Base>, b: &Option<Base>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => without_history(a) == without_history(b),
+        (a, b) => a.is_none() && b.is_none(),
+    }
+}
+
+fn without_history(task: &Base) -> Base {
+    Base { history: Vec::new(), ..task.clone() }
+}
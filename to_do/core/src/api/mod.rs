@@ -1 +1,13 @@
-pub mod basic_actions;
\ No newline at end of file
+#[cfg(feature = "assistant")]
+pub mod assistant;
+pub mod audit;
+pub mod basic_actions;
+pub mod dedupe;
+pub mod doctor;
+pub mod history;
+pub mod plan;
+pub mod search;
+pub mod sorting;
+pub mod suggest;
+pub mod tags;
+pub mod store;
\ No newline at end of file
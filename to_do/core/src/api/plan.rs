@@ -0,0 +1,259 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Utc, Weekday};
+use dal::storage::Storage;
+use std::collections::HashMap;
+use std::env;
+
+use crate::structs::base::Base;
+use crate::enums::TaskStatus;
+use crate::errors::TaskError;
+use super::basic_actions::creates::ItemTypes;
+use super::basic_actions::edits::{edit, TaskChanges};
+
+/// The days and time of day tasks can be scheduled on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkingHours {
+    pub days: Vec<Weekday>,
+    /// Planned tasks are due at this time (UTC) on their day.
+    pub end: NaiveTime,
+}
+
+impl Default for WorkingHours {
+    fn default() -> Self {
+        WorkingHours {
+            days: vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri],
+            end: NaiveTime::from_hms_opt(17, 0, 0).unwrap_or_default(),
+        }
+    }
+}
+
+impl WorkingHours {
+    /// Reads `TODO_WORK_DAYS` (a range or list of weekdays, e.g. `mon-fri`
+    /// or `mon,wed,sat`) and `TODO_WORK_END` (`HH:MM`, UTC). Unset variables
+    /// keep the default of Monday to Friday until 17:00.
+    pub fn from_env() -> Result<WorkingHours, TaskError> {
+        let mut hours = WorkingHours::default();
+        if let Ok(days) = env::var("TODO_WORK_DAYS") {
+            hours.days = parse_days(&days).ok_or_else(|| TaskError::InvalidWorkingHours(format!(
+                "TODO_WORK_DAYS={} (expected weekdays such as mon-fri or mon,wed,fri)", days
+            )))?;
+        }
+        if let Ok(end) = env::var("TODO_WORK_END") {
+            hours.end = NaiveTime::parse_from_str(end.trim(), "%H:%M").map_err(|_| TaskError::InvalidWorkingHours(format!(
+                "TODO_WORK_END={} (expected HH:MM)", end
+            )))?;
+        }
+        Ok(hours)
+    }
+}
+
+fn parse_day(day: &str) -> Option<Weekday> {
+    day.trim().parse().ok()
+}
+
+fn parse_days(days: &str) -> Option<Vec<Weekday>> {
+    let mut parsed = Vec::new();
+    for part in days.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (mut day, last) = (parse_day(first)?, parse_day(last)?);
+                while day != last {
+                    parsed.push(day);
+                    day = day.succ();
+                }
+                parsed.push(last);
+            }
+            None => parsed.push(parse_day(part)?),
+        }
+    }
+    parsed.sort_by_key(|day| day.num_days_from_monday());
+    parsed.dedup();
+    Some(parsed).filter(|days| !days.is_empty())
+}
+
+/// Parses a planning horizon such as `7d`, `2w` or `10` (days).
+pub fn parse_horizon(horizon: &str) -> Result<u32, TaskError> {
+    let text = horizon.trim().to_lowercase();
+    let (number, per_unit) = match text.strip_suffix('w') {
+        Some(weeks) => (weeks, 7),
+        None => (text.strip_suffix('d').unwrap_or(&text), 1),
+    };
+    number.parse::<u32>()
+        .ok()
+        .and_then(|n| n.checked_mul(per_unit))
+        .filter(|days| *days > 0)
+        .ok_or_else(|| TaskError::InvalidHorizon(horizon.to_string()))
+}
+
+/// A due date proposed for an unscheduled task.
+pub struct Proposal {
+    pub item: ItemTypes,
+    pub due: DateTime<Utc>,
+}
+
+/// The outcome of `plan`: the proposed due dates, in date order, and the
+/// tasks that didn't fit within the horizon.
+pub struct Plan {
+    pub proposals: Vec<Proposal>,
+    pub unplaced: Vec<ItemTypes>,
+}
+
+/// Proposes due dates for the pending tasks that have none, without
+/// changing anything.
+///
+/// Each task counts as one unit of work, and each working day within the
+/// next `horizon_days` days (today included, if its working hours aren't
+/// over yet) takes at most `per_day` tasks. Tasks already due on a day count
+/// towards it. More urgent tasks, then older ones, get the earliest days.
+///
+/// # Returns
+///
+/// * `Ok(Plan)` - The proposals and the tasks left without a day
+/// * `Err(TaskError)` - If the store can't be read
+pub fn plan(store: &dyn Storage<Base>, horizon_days: u32, per_day: usize, hours: &WorkingHours) -> Result<Plan, TaskError> {
+    plan_at(store, Utc::now(), horizon_days, per_day, hours)
+}
+
+/// `plan` as of `now`.
+fn plan_at(store: &dyn Storage<Base>, now: DateTime<Utc>, horizon_days: u32, per_day: usize, hours: &WorkingHours) -> Result<Plan, TaskError> {
+    let tasks = store.get_all()?;
+    let mut load: HashMap<NaiveDate, usize> = HashMap::new();
+    for due in tasks.values().filter(|task| task.status == TaskStatus::PENDING).filter_map(|task| task.due) {
+        *load.entry(due.date_naive()).or_default() += 1;
+    }
+    let days: Vec<DateTime<Utc>> = (0..horizon_days)
+        .filter_map(|offset| now.date_naive().checked_add_signed(Duration::days(i64::from(offset))))
+        .filter(|date| hours.days.contains(&date.weekday()))
+        .map(|date| date.and_time(hours.end).and_utc())
+        .filter(|due| *due > now)
+        .collect();
+    let mut unscheduled: Vec<Base> = tasks.into_values()
+        .filter(|task| task.status == TaskStatus::PENDING && task.due.is_none())
+        .collect();
+    unscheduled.sort_by(|a, b| {
+        b.priority.cmp(&a.priority)
+            .then_with(|| a.created_at.cmp(&b.created_at))
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    let mut proposals = Vec::new();
+    let mut unplaced = Vec::new();
+    for task in unscheduled {
+        let day = days.iter().find(|due| load.get(&due.date_naive()).copied().unwrap_or(0) < per_day);
+        match day {
+            Some(due) => {
+                *load.entry(due.date_naive()).or_default() += 1;
+                proposals.push(Proposal { item: ItemTypes::from_base(task), due: *due });
+            }
+            None => unplaced.push(ItemTypes::from_base(task)),
+        }
+    }
+    proposals.sort_by_key(|proposal| proposal.due);
+    Ok(Plan { proposals, unplaced })
+}
+
+/// Sets the proposed due dates.
+///
+/// # Returns
+///
+/// * `Ok(usize)` - How many tasks were scheduled
+/// * `Err(TaskError)` - If a task can't be updated
+pub fn apply(store: &dyn Storage<Base>, plan: &Plan) -> Result<usize, TaskError> {
+    for proposal in &plan.proposals {
+        let changes = TaskChanges { due: Some(Some(proposal.due)), ..TaskChanges::default() };
+        edit(store, &proposal.item.base().id, changes)?;
+    }
+    Ok(plan.proposals.len())
+}
+
+/// Renders the proposals one per line under their day, followed by the
+/// tasks that didn't fit.
+pub fn render_plan(plan: &Plan) -> String {
+    let mut out = String::new();
+    let mut current = None;
+    for proposal in &plan.proposals {
+        let day = proposal.due.date_naive();
+        if current != Some(day) {
+            out.push_str(&format!("{}\n", proposal.due.format("%a %Y-%m-%d (due %H:%M)")));
+            current = Some(day);
+        }
+        out.push_str(&format!("  {} [{}]\n", proposal.item, proposal.item.base().priority));
+    }
+    if plan.proposals.is_empty() && plan.unplaced.is_empty() {
+        out.push_str("Nothing to plan; every pending task has a due date\n");
+    }
+    if !plan.unplaced.is_empty() {
+        out.push_str(&format!("No room within the horizon for {} task(s):\n", plan.unplaced.len()));
+        for item in &plan.unplaced {
+            out.push_str(&format!("  {}\n", item));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::Priority;
+    use crate::testing::{store_with, task};
+    use chrono::TimeZone;
+
+    /// 2026-01-07 is a Wednesday.
+    fn on(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 1, day, hour, 0, 0).unwrap()
+    }
+
+    fn days(plan: &Plan) -> Vec<(String, u32)> {
+        plan.proposals.iter().map(|proposal| (proposal.item.base().id.clone(), proposal.due.day())).collect()
+    }
+
+    #[test]
+    fn days_are_parsed_in_week_order_without_repeats() {
+        use Weekday::*;
+        assert_eq!(parse_days("mon-fri"), Some(vec![Mon, Tue, Wed, Thu, Fri]));
+        assert_eq!(parse_days("fri-mon"), Some(vec![Mon, Fri, Sat, Sun]));
+        assert_eq!(parse_days("sat, mon,wed,mon"), Some(vec![Mon, Wed, Sat]));
+        assert_eq!(parse_days("mon-wed,tue"), Some(vec![Mon, Tue, Wed]));
+        assert_eq!(parse_days("mon,funday"), None);
+        assert_eq!(parse_days(""), None);
+    }
+
+    #[test]
+    fn horizons_are_days_or_weeks() {
+        assert_eq!(parse_horizon("10").unwrap(), 10);
+        assert_eq!(parse_horizon("7d").unwrap(), 7);
+        assert_eq!(parse_horizon(" 2W ").unwrap(), 14);
+        for horizon in ["0", "0w", "-1d", "d", "3m", "4294967295w"] {
+            assert!(matches!(parse_horizon(horizon), Err(TaskError::InvalidHorizon(_))), "{} was accepted", horizon);
+        }
+    }
+
+    #[test]
+    fn tasks_fill_working_days_in_order_and_skip_full_ones() {
+        let urgent = Base { priority: Priority::Urgent, ..task("urgent", "Urgent") };
+        let due_thursday = Base { due: Some(on(8, 12)), ..task("set", "Already due") };
+        let store = store_with("plan-fill", vec![task("a", "A"), task("b", "B"), urgent, due_thursday]);
+        // Wednesday morning, one task a day: today, Thursday is taken, then Friday and Monday.
+        let plan = plan_at(&*store, on(7, 9), 7, 1, &WorkingHours::default()).unwrap();
+        assert_eq!(days(&plan), [("urgent".to_string(), 7), ("a".to_string(), 9), ("b".to_string(), 12)]);
+        assert!(plan.proposals.iter().all(|proposal| proposal.due.time() == WorkingHours::default().end));
+        assert!(plan.unplaced.is_empty());
+    }
+
+    #[test]
+    fn today_is_skipped_once_its_hours_are_over() {
+        let store = store_with("plan-cut-off", vec![task("a", "A")]);
+        let plan = plan_at(&*store, on(7, 18), 7, 1, &WorkingHours::default()).unwrap();
+        assert_eq!(days(&plan), [("a".to_string(), 8)]);
+    }
+
+    #[test]
+    fn tasks_beyond_the_horizon_are_left_unplaced() {
+        let store = store_with("plan-overflow", vec![task("a", "A"), task("b", "B"), task("c", "C")]);
+        // Friday evening with two days' horizon: only the weekend, which isn't worked.
+        let plan = plan_at(&*store, on(9, 18), 2, 5, &WorkingHours::default()).unwrap();
+        assert!(plan.proposals.is_empty());
+        assert_eq!(plan.unplaced.len(), 3);
+        let plan = plan_at(&*store, on(9, 9), 2, 2, &WorkingHours::default()).unwrap();
+        assert_eq!(plan.proposals.len(), 2);
+        assert_eq!(plan.unplaced.iter().map(|item| item.base().id.as_str()).collect::<Vec<_>>(), ["c"]);
+    }
+}
@@ -0,0 +1,171 @@
+use dal::storage::Storage;
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+use unicode_normalization::char::is_combining_mark;
+
+use crate::structs::base::Base;
+use crate::errors::TaskError;
+use crate::normalize::{fold, strip_accents};
+use super::basic_actions::creates::ItemTypes;
+use super::sorting::{sort, SortOrder};
+
+/// A task that matched a search, with where the matches are.
+pub struct SearchHit {
+    pub item: ItemTypes,
+    /// Byte ranges of the matches in the title.
+    pub title_matches: Vec<Range<usize>>,
+    /// Description lines containing a match, with the ranges within each line.
+    pub description_matches: Vec<(String, Vec<Range<usize>>)>,
+}
+
+/// Builds a matcher for `query`, ignoring case and accents: a plain
+/// substring, or a regular expression when `regex` is set.
+///
+/// It's meant for text folded by `fold`, as `search` does, so a plain
+/// `query` is folded too; a regular expression only has its accents
+/// stripped, since folding would change escapes such as `\W`.
+///
+/// # Returns
+///
+/// * `Ok(Regex)` - The compiled matcher
+/// * `Err(TaskError::InvalidRegex)` - If `regex` is set and `query` isn't a valid expression
+pub fn matcher(query: &str, regex: bool) -> Result<Regex, TaskError> {
+    let pattern = if regex { strip_accents(query) } else { regex::escape(&fold(query)) };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| TaskError::InvalidRegex(e.to_string()))
+}
+
+/// Scans every stored task for `matcher` in its title and description.
+///
+/// # Returns
+///
+/// * `Ok(Vec<SearchHit>)` - The matching tasks, in the default `list` order
+/// * `Err(TaskError)` - If the store can't be read
+pub fn search(store: &dyn Storage<Base>, matcher: &Regex) -> Result<Vec<SearchHit>, TaskError> {
+    let mut tasks: Vec<Base> = store.get_all()?.into_values().collect();
+    sort(&mut tasks, &SortOrder::default());
+    let find = |text: &str| find_folded(matcher, text);
+    let hits = tasks.into_iter()
+        .filter_map(|base| {
+            let title_matches = find(&base.title);
+            let description_matches: Vec<(String, Vec<Range<usize>>)> = base.description.iter()
+                .flat_map(|description| description.lines())
+                .filter_map(|line| {
+                    let ranges = find(line);
+                    (!ranges.is_empty()).then(|| (line.to_string(), ranges))
+                })
+                .collect();
+            if title_matches.is_empty() && description_matches.is_empty() {
+                return None;
+            }
+            Some(SearchHit { item: ItemTypes::from_base(base), title_matches, description_matches })
+        })
+        .collect();
+    Ok(hits)
+}
+
+/// Finds `matcher` in `text` folded by `fold`, and returns the byte ranges
+/// of the matches in `text` itself, each widened to whole characters (with
+/// the combining marks that follow them).
+fn find_folded(matcher: &Regex, text: &str) -> Vec<Range<usize>> {
+    // A character is folded together with its marks, which only count as
+    // accents on some letters (see `strip_accents`).
+    let starts: Vec<usize> = text.char_indices()
+        .filter(|&(i, c)| i == 0 || !is_combining_mark(c))
+        .map(|(i, _)| i)
+        .collect();
+    // The range in `text` of the character each folded byte came from.
+    let mut folded = String::new();
+    let mut origin = Vec::new();
+    for (n, &start) in starts.iter().enumerate() {
+        let end = starts.get(n + 1).copied().unwrap_or(text.len());
+        let piece = fold(&text[start..end]);
+        folded.push_str(&piece);
+        origin.extend(std::iter::repeat_n(start..end, piece.len()));
+    }
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for m in matcher.find_iter(&folded).filter(|m| !m.is_empty()) {
+        let range = origin[m.start()].start..origin[m.end() - 1].end;
+        // Two matches in what one character folded to share it.
+        match ranges.last_mut() {
+            Some(last) if range.start < last.end => last.end = last.end.max(range.end),
+            _ => ranges.push(range),
+        }
+    }
+    ranges
+}
+
+/// Renders hits as `<id> <title>` lines, each followed by its matching
+/// description lines, indented.
+///
+/// # Arguments
+///
+/// * `color` - Highlight the matches with ANSI escapes
+pub fn render_hits(hits: &[SearchHit], color: bool) -> String {
+    let mut out = String::new();
+    for hit in hits {
+        let base = hit.item.base();
+        out.push_str(&format!("{} {}\n", base.id, highlight(&base.title, &hit.title_matches, color)));
+        for (line, ranges) in &hit.description_matches {
+            out.push_str(&format!("    {}\n", highlight(line, ranges, color)));
+        }
+    }
+    out
+}
+
+/// Wraps each range of `text` in bold yellow, or returns `text` as is
+/// without colour.
+fn highlight(text: &str, ranges: &[Range<usize>], color: bool) -> String {
+    if !color {
+        return text.to_string();
+    }
+    let mut out = String::new();
+    let mut last = 0;
+    for range in ranges {
+        out.push_str(&text[last..range.start]);
+        out.push_str(&format!("\x1b[1;33m{}\x1b[0m", &text[range.clone()]));
+        last = range.end;
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn found<'a>(query: &str, regex: bool, text: &'a str) -> Vec<&'a str> {
+        find_folded(&matcher(query, regex).unwrap(), text).into_iter().map(|range| &text[range]).collect()
+    }
+
+    #[test]
+    fn ignores_case_and_accents_both_ways() {
+        assert_eq!(found("cafe", false, "Café and CAFE"), vec!["Café", "CAFE"]);
+        assert_eq!(found("café", false, "cafe"), vec!["cafe"]);
+        assert_eq!(found("creme", false, "cre\u{301}me brûlée"), vec!["cre\u{301}me"]);
+    }
+
+    #[test]
+    fn ranges_point_into_the_original_text() {
+        let text = "Ärger über Öl";
+        assert_eq!(found("ol", false, text), vec!["Öl"]);
+        assert_eq!(found(r"\bu\w+", true, text), vec!["über"]);
+        assert_eq!(found(r"\W+", true, "a – b"), vec![" – "]);
+    }
+
+    #[test]
+    fn characters_that_fold_to_more_are_highlighted_whole() {
+        // 'ß' folds to "ss".
+        assert_eq!(found("STRASSE", false, "Straße 5"), vec!["Straße"]);
+        assert_eq!(found("s", false, "Straße"), vec!["S", "ß"]);
+        assert_eq!(found("straße", false, "STRASSE"), vec!["STRASSE"]);
+    }
+
+    #[test]
+    fn marks_are_kept_outside_latin_greek_and_cyrillic() {
+        assert_eq!(found("कताब", false, "किताब"), Vec::<&str>::new());
+        assert_eq!(found("किताब", false, "नई किताब"), vec!["किताब"]);
+    }
+}
@@ -0,0 +1,144 @@
+use std::cmp::{Ordering, Reverse};
+
+use crate::structs::base::Base;
+use crate::enums::TaskStatus;
+use crate::errors::TaskError;
+use crate::normalize::match_key;
+
+/// The field tasks are ordered by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    /// Alphabetical, ignoring case and accents.
+    Title,
+    /// Oldest first.
+    Created,
+    /// Soonest first; tasks without a due date come last.
+    Due,
+    /// Most urgent first.
+    #[default]
+    Priority,
+    /// Pending before done.
+    Status,
+    /// Storage order (byte-wise by id). UUIDv7 ids sort by creation time.
+    Id,
+}
+
+impl SortKey {
+    /// Parses `title`, `created`, `due`, `priority`, `status` or `id`, ignoring case.
+    pub fn from_string(key: &str) -> Result<SortKey, TaskError> {
+        match key.to_lowercase().as_str() {
+            "title" => Ok(SortKey::Title),
+            "created" => Ok(SortKey::Created),
+            "due" => Ok(SortKey::Due),
+            "priority" => Ok(SortKey::Priority),
+            "status" => Ok(SortKey::Status),
+            "id" => Ok(SortKey::Id),
+            _ => Err(TaskError::InvalidSortKey(key.to_string())),
+        }
+    }
+}
+
+/// How to order a list of tasks.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SortOrder {
+    pub key: SortKey,
+    /// Reverse the key's natural order. Tasks without a due date stay last.
+    pub reverse: bool,
+}
+
+/// Sorts `tasks` by `order`. Ties are broken by priority, then creation
+/// time, then id, so the result is stable across runs.
+pub fn sort(tasks: &mut [Base], order: &SortOrder) {
+    // Match keys allocate, so they're worked out once per task rather than
+    // on every comparison.
+    let tie_break = |task: &Base| (Reverse(task.priority), task.created_at, task.id.clone());
+    match (order.key, order.reverse) {
+        (SortKey::Title, false) => tasks.sort_by_cached_key(|task| (match_key(&task.title), tie_break(task))),
+        (SortKey::Title, true) => tasks.sort_by_cached_key(|task| (Reverse(match_key(&task.title)), tie_break(task))),
+        _ => tasks.sort_by(|a, b| compare(a, b, order)),
+    }
+}
+
+/// Compares two tasks by any key but `Title`, which `sort` handles itself.
+fn compare(a: &Base, b: &Base, order: &SortOrder) -> Ordering {
+    let primary = match order.key {
+        SortKey::Title => unreachable!("titles are sorted by cached key"),
+        SortKey::Created => a.created_at.cmp(&b.created_at),
+        SortKey::Priority => b.priority.cmp(&a.priority),
+        SortKey::Status => status_rank(&a.status).cmp(&status_rank(&b.status)),
+        SortKey::Id => a.id.cmp(&b.id),
+        SortKey::Due => match (a.due, b.due) {
+            (Some(a), Some(b)) if order.reverse => b.cmp(&a),
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        },
+    };
+    let primary = if order.reverse && order.key != SortKey::Due { primary.reverse() } else { primary };
+    primary
+        .then_with(|| b.priority.cmp(&a.priority))
+        .then_with(|| a.created_at.cmp(&b.created_at))
+        .then_with(|| a.id.cmp(&b.id))
+}
+
+fn status_rank(status: &TaskStatus) -> u8 {
+    match status {
+        TaskStatus::PENDING => 0,
+        TaskStatus::DONE => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::Priority;
+    use crate::testing::task;
+    use chrono::{TimeZone, Utc};
+
+    fn at(day: u32) -> Option<chrono::DateTime<Utc>> {
+        Some(Utc.with_ymd_and_hms(2026, 1, day, 9, 0, 0).unwrap())
+    }
+
+    fn sorted(mut tasks: Vec<Base>, key: SortKey, reverse: bool) -> Vec<String> {
+        sort(&mut tasks, &SortOrder { key, reverse });
+        tasks.into_iter().map(|task| task.id).collect()
+    }
+
+    fn tasks() -> Vec<Base> {
+        vec![
+            Base { priority: Priority::Low, created_at: at(1), due: at(20), ..task("a", "Écrire") },
+            Base { priority: Priority::Urgent, created_at: at(2), due: None, status: TaskStatus::DONE, ..task("b", "apples") },
+            Base { priority: Priority::Medium, created_at: at(3), due: at(10), ..task("c", "Zebra") },
+            Base { priority: Priority::High, created_at: at(4), due: None, ..task("d", "BANANAS") },
+        ]
+    }
+
+    #[test]
+    fn each_key_orders_by_its_field() {
+        assert_eq!(sorted(tasks(), SortKey::Title, false), ["b", "d", "a", "c"]);
+        assert_eq!(sorted(tasks(), SortKey::Created, false), ["a", "b", "c", "d"]);
+        assert_eq!(sorted(tasks(), SortKey::Priority, false), ["b", "d", "c", "a"]);
+        assert_eq!(sorted(tasks(), SortKey::Due, false), ["c", "a", "b", "d"]);
+        assert_eq!(sorted(tasks(), SortKey::Status, false), ["d", "c", "a", "b"]);
+        assert_eq!(sorted(tasks(), SortKey::Id, false), ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn reverse_flips_the_key_but_not_the_ties_or_missing_due_dates() {
+        assert_eq!(sorted(tasks(), SortKey::Title, true), ["c", "a", "d", "b"]);
+        assert_eq!(sorted(tasks(), SortKey::Priority, true), ["a", "c", "d", "b"]);
+        assert_eq!(sorted(tasks(), SortKey::Due, true), ["a", "c", "b", "d"]);
+        assert_eq!(sorted(tasks(), SortKey::Status, true), ["b", "d", "c", "a"]);
+    }
+
+    #[test]
+    fn ties_go_to_priority_then_age_then_id() {
+        let same = |id: &str, priority: Priority, day: u32| Base { priority, created_at: at(day), ..task(id, "Café") };
+        let tied = vec![same("4", Priority::Low, 1), same("3", Priority::High, 2), same("2", Priority::High, 1), same("1", Priority::High, 1)];
+        assert_eq!(sorted(tied.clone(), SortKey::Title, false), ["1", "2", "3", "4"]);
+        assert_eq!(sorted(tied.clone(), SortKey::Title, true), ["1", "2", "3", "4"]);
+        let renamed: Vec<Base> = tied.into_iter().map(|task| Base { title: "CAFE".to_string(), ..task }).collect();
+        assert_eq!(sorted(renamed, SortKey::Status, false), ["1", "2", "3", "4"]);
+    }
+}
@@ -0,0 +1,164 @@
+use dal::ids::IdScheme;
+use dal::json_file::JsonFileStore;
+use dal::storage::Storage;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+
+use crate::structs::{base::Base, pending::Pending};
+use crate::enums::TaskStatus;
+use crate::errors::TaskError;
+use crate::normalize::normalize_title;
+
+/// Diagnostic facts about the configured storage backend.
+pub struct StoreInfo {
+    /// Backend-specific facts (backend, location, schema, ...), in display order.
+    pub details: Vec<(&'static str, String)>,
+    /// Number of stored tasks with each status.
+    pub counts: Vec<(TaskStatus, usize)>,
+    pub durability: String,
+}
+
+/// Collects diagnostics about `store`.
+///
+/// # Returns
+///
+/// * `Ok(StoreInfo)` - The collected diagnostics
+/// * `Err(TaskError)` - If the store can't be read or inspected
+pub fn info(store: &dyn Storage<Base>) -> Result<StoreInfo, TaskError> {
+    let tasks = store.get_all()?;
+    let counts = [TaskStatus::PENDING, TaskStatus::DONE]
+        .into_iter()
+        .map(|status| {
+            let count = tasks.values().filter(|task| task.status == status).count();
+            (status, count)
+        })
+        .collect();
+    Ok(StoreInfo {
+        details: store.diagnostics()?,
+        counts,
+        durability: store.durability(),
+    })
+}
+
+/// Renders store diagnostics as labelled lines.
+pub fn render_info(info: &StoreInfo) -> String {
+    let counts: Vec<String> = info.counts.iter()
+        .map(|(status, count)| format!("{} {}", count, status))
+        .collect();
+    let total: usize = info.counts.iter().map(|(_, count)| count).sum();
+    let mut lines: Vec<(&str, String)> = info.details.iter()
+        .map(|(label, value)| (*label, value.clone()))
+        .collect();
+    lines.push(("Items", format!("{} ({})", total, counts.join(", "))));
+    lines.push(("Durability", info.durability.clone()));
+    let width = lines.iter().map(|(label, _)| label.len()).max().unwrap_or(0) + 1;
+    lines.iter()
+        .map(|(label, value)| format!("{:<width$} {}\n", format!("{}:", label), value, width = width))
+        .collect()
+}
+
+/// The configured JSON store, if it's still in the original
+/// `title -> status` layout (e.g. `{"coding": "PENDING"}`).
+///
+/// Only the first entry is read to tell the layouts apart, so it's cheap to
+/// call before every command.
+pub fn legacy_store() -> Option<JsonFileStore> {
+    let backend = env::var("STORE_BACKEND").unwrap_or_else(|_| "json-file".to_string());
+    let path = env::var("JSON_STORE_PATH").unwrap_or_else(|_| "tasks.json".to_string());
+    if !matches!(backend.to_lowercase().as_str(), "json-file" | "json") || !Path::new(&path).exists() {
+        return None;
+    }
+    // A bad setting is left for opening the store to report.
+    let store = JsonFileStore::from_env().ok()?;
+    // The journal came after the layout change, so a store with one is
+    // already converted (and reading it would compact the journal).
+    if store.journal_len() > 0 {
+        return None;
+    }
+    let legacy = match store.iter_all::<Value>() {
+        Ok(mut entries) => matches!(entries.next(), Some(Ok((_, Value::String(_))))),
+        // Left for opening the store to report.
+        Err(_) => false,
+    };
+    legacy.then_some(store)
+}
+
+/// Converts the entries of a store in the old layout to tasks keyed by
+/// generated ids (see `ID_SCHEME`).
+///
+/// # Returns
+///
+/// * `Ok(HashMap<String, Base>)` - The converted tasks
+/// * `Err(TaskError)` - If an entry isn't a known status, or `ID_SCHEME` is invalid
+pub fn convert_legacy(old: HashMap<String, Value>) -> Result<HashMap<String, Base>, TaskError> {
+    let mut old: Vec<(String, Value)> = old.into_iter().collect();
+    old.sort_by(|a, b| a.0.cmp(&b.0));
+    let ids = IdScheme::from_env()?.generator(std::iter::empty());
+    let mut tasks = HashMap::new();
+    for (title, status) in old {
+        let status = match status {
+            Value::String(status) => TaskStatus::from_string(&status)?,
+            other => return Err(TaskError::InvalidStatus(other.to_string())),
+        };
+        let id = ids.next_id();
+        let mut task = Pending::new(&id, &normalize_title(&title)).super_struct;
+        task.status = status;
+        // When they were created was never recorded.
+        task.created_at = None;
+        tasks.insert(id, task);
+    }
+    Ok(tasks)
+}
+
+/// Converts a JSON store still in the old `title -> status` layout (see
+/// `legacy_store`) and writes it back once. Stores in any other layout are
+/// left alone.
+///
+/// # Returns
+///
+/// * `Ok(usize)` - How many tasks were converted; 0 if there was nothing to convert
+/// * `Err(TaskError)` - If a status is unknown, or the store can't be read or written
+pub fn migrate_legacy() -> Result<usize, TaskError> {
+    let Some(store) = legacy_store() else {
+        return Ok(0);
+    };
+    let mut converted = 0;
+    store.rewrite(|old: HashMap<String, Value>| {
+        // Another process may have converted it since the check.
+        if !old.values().all(Value::is_string) {
+            return Ok(None);
+        }
+        let tasks = convert_legacy(old)?;
+        converted = tasks.len();
+        Ok::<_, TaskError>(Some(tasks))
+    })?;
+    Ok(converted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn legacy_entries_become_tasks_with_ids() {
+        let old = HashMap::from([
+            ("coding".to_string(), json!("PENDING")),
+            ("washing  up".to_string(), json!("done")),
+        ]);
+        let mut tasks: Vec<Base> = convert_legacy(old).unwrap().into_values().collect();
+        tasks.sort_by(|a, b| a.title.cmp(&b.title));
+        let converted: Vec<(&str, TaskStatus)> = tasks.iter().map(|task| (task.title.as_str(), task.status.clone())).collect();
+        assert_eq!(converted, vec![("coding", TaskStatus::PENDING), ("washing up", TaskStatus::DONE)]);
+        assert_ne!(tasks[0].id, tasks[1].id);
+        assert!(tasks.iter().all(|task| task.created_at.is_none()));
+    }
+
+    #[test]
+    fn unknown_legacy_statuses_are_rejected() {
+        let old = HashMap::from([("coding".to_string(), json!("MAYBE"))]);
+        assert!(matches!(convert_legacy(old), Err(TaskError::InvalidStatus(status)) if status == "MAYBE"));
+    }
+}
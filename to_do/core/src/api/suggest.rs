@@ -0,0 +1,147 @@
+use chrono::{DateTime, Utc};
+use dal::storage::Storage;
+use std::collections::HashMap;
+
+use crate::structs::base::Base;
+use crate::enums::{Priority, TaskStatus};
+use crate::errors::TaskError;
+use super::basic_actions::creates::ItemTypes;
+
+/// Due dates further away than this don't add to the score.
+const DUE_HORIZON_DAYS: f64 = 14.0;
+/// Tasks this old get the full age score.
+const AGE_CAP_DAYS: f64 = 30.0;
+/// Blocking this many open tasks gets the full blocking score.
+const BLOCKING_CAP: f64 = 3.0;
+
+/// How much each factor counts towards a suggestion score.
+///
+/// Every factor is first scaled to 0..=1, so a weight is the most that
+/// factor can add.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Weights {
+    /// Closeness of the due date; overdue tasks score highest.
+    pub due: f64,
+    pub priority: f64,
+    /// Time since the task was created.
+    pub age: f64,
+    /// Open parent tasks waiting for this subtask.
+    pub blocking: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights { due: 3.0, priority: 2.0, age: 1.0, blocking: 1.0 }
+    }
+}
+
+impl Weights {
+    /// Parses comma-separated `factor=weight` pairs, e.g. `due=5,age=0`.
+    /// Factors that aren't given keep their default weight.
+    pub fn from_string(weights: &str) -> Result<Weights, TaskError> {
+        let mut parsed = Weights::default();
+        for pair in weights.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let invalid = || TaskError::InvalidWeights(pair.to_string());
+            let (factor, weight) = pair.split_once('=').ok_or_else(invalid)?;
+            let weight: f64 = weight.trim().parse().ok().filter(|w: &f64| w.is_finite()).ok_or_else(invalid)?;
+            match factor.trim().to_lowercase().as_str() {
+                "due" => parsed.due = weight,
+                "priority" => parsed.priority = weight,
+                "age" => parsed.age = weight,
+                "blocking" => parsed.blocking = weight,
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// An open task with its score and what the score is made of.
+pub struct Suggestion {
+    pub item: ItemTypes,
+    pub score: f64,
+    /// Human-readable contributions, largest first, e.g. "due in 2d (+2.6)".
+    pub reasons: Vec<String>,
+}
+
+/// Ranks the pending tasks by `weights`, highest score first.
+///
+/// # Returns
+///
+/// * `Ok(Vec<Suggestion>)` - At most `limit` suggestions
+/// * `Err(TaskError)` - If the store can't be read
+pub fn suggest(store: &dyn Storage<Base>, weights: &Weights, limit: usize) -> Result<Vec<Suggestion>, TaskError> {
+    let tasks = store.get_all()?;
+    let now = Utc::now();
+    let mut suggestions: Vec<Suggestion> = tasks.values()
+        .filter(|task| task.status == TaskStatus::PENDING)
+        .map(|task| score(task, &tasks, weights, now))
+        .collect();
+    suggestions.sort_by(|a, b| {
+        b.score.total_cmp(&a.score).then_with(|| a.item.base().id.cmp(&b.item.base().id))
+    });
+    suggestions.truncate(limit);
+    Ok(suggestions)
+}
+
+fn score(task: &Base, tasks: &HashMap<String, Base>, weights: &Weights, now: DateTime<Utc>) -> Suggestion {
+    let mut parts: Vec<(f64, String)> = Vec::new();
+    if let Some(due) = task.due {
+        let days = (due - now).num_minutes() as f64 / (24.0 * 60.0);
+        let factor = if days < 0.0 { 1.0 } else { (1.0 - days / DUE_HORIZON_DAYS).max(0.0) };
+        let label = if days < 0.0 { format!("overdue by {:.0}d", -days) } else { format!("due in {:.0}d", days) };
+        parts.push((weights.due * factor, label));
+    }
+    let priority = match task.priority {
+        Priority::Low => 0.0,
+        Priority::Medium => 1.0 / 3.0,
+        Priority::High => 2.0 / 3.0,
+        Priority::Urgent => 1.0,
+    };
+    parts.push((weights.priority * priority, task.priority.to_string()));
+    if let Some(created_at) = task.created_at {
+        let days = (now - created_at).num_days().max(0) as f64;
+        parts.push((weights.age * (days / AGE_CAP_DAYS).min(1.0), format!("{:.0}d old", days)));
+    }
+    let blocked = open_ancestors(task, tasks);
+    if blocked > 0 {
+        parts.push((weights.blocking * (blocked as f64 / BLOCKING_CAP).min(1.0), format!("blocks {}", blocked)));
+    }
+    parts.sort_by(|a, b| b.0.total_cmp(&a.0));
+    Suggestion {
+        item: ItemTypes::from_base(task.clone()),
+        score: parts.iter().map(|(points, _)| points).sum(),
+        reasons: parts.into_iter().map(|(points, label)| format!("{} ({:+.1})", label, points)).collect(),
+    }
+}
+
+/// Counts the pending tasks above `task` in the subtask hierarchy.
+fn open_ancestors(task: &Base, tasks: &HashMap<String, Base>) -> usize {
+    let mut count = 0;
+    let mut current = task;
+    // The length check guards against a hand-edited cycle.
+    while let Some(parent) = current.parent_id.as_ref().and_then(|id| tasks.get(id)) {
+        if count > tasks.len() {
+            break;
+        }
+        if parent.status == TaskStatus::PENDING {
+            count += 1;
+        }
+        current = parent;
+    }
+    count
+}
+
+/// Renders suggestions as numbered lines with their score and reasons.
+pub fn render_suggestions(suggestions: &[Suggestion]) -> String {
+    suggestions.iter()
+        .enumerate()
+        .map(|(i, suggestion)| format!(
+            "{}. {} [{:.1}]\n   {}\n",
+            i + 1,
+            suggestion.item,
+            suggestion.score,
+            suggestion.reasons.join(", "),
+        ))
+        .collect()
+}
@@ -0,0 +1,63 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::structs::base::Base;
+use crate::normalize::{jaccard, normalize_tag, trigrams};
+
+/// Tasks whose titles are less similar than this don't suggest their tags.
+const MIN_SIMILARITY: f64 = 0.2;
+
+/// A tag used on tasks with titles similar to a new one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagSuggestion {
+    pub tag: String,
+    /// Sum of the title similarities (0..=1 each) of the tasks carrying it.
+    pub score: f64,
+    /// How many similar tasks carry it.
+    pub matches: usize,
+}
+
+/// Suggests tags for a task titled `title` from the tags of `tasks` with
+/// similar titles, best first.
+///
+/// Titles are compared by the trigrams of their match keys (Jaccard
+/// similarity), so word order, case, accents and small typos matter little.
+///
+/// # Arguments
+///
+/// * `tags` - Tags the task already has; they aren't suggested again
+///
+/// # Returns
+///
+/// At most `limit` suggestions.
+pub fn suggest_tags(tasks: &HashMap<String, Base>, title: &str, tags: &[String], limit: usize) -> Vec<TagSuggestion> {
+    let wanted = trigrams(title);
+    let present: HashSet<String> = tags.iter().map(|tag| normalize_tag(tag)).collect();
+    let mut by_tag: HashMap<String, TagSuggestion> = HashMap::new();
+    for task in tasks.values() {
+        if task.tags.is_empty() {
+            continue;
+        }
+        let similarity = jaccard(&wanted, &trigrams(&task.title));
+        if similarity < MIN_SIMILARITY {
+            continue;
+        }
+        for tag in task.tags.iter().filter(|tag| !present.contains(*tag)) {
+            let suggestion = by_tag.entry(tag.clone())
+                .or_insert_with(|| TagSuggestion { tag: tag.clone(), score: 0.0, matches: 0 });
+            suggestion.score += similarity;
+            suggestion.matches += 1;
+        }
+    }
+    let mut suggestions: Vec<TagSuggestion> = by_tag.into_values().collect();
+    suggestions.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.tag.cmp(&b.tag)));
+    suggestions.truncate(limit);
+    suggestions
+}
+
+/// Renders suggestions on one line, e.g. `work (3 similar), home (1 similar)`.
+pub fn render_tag_suggestions(suggestions: &[TagSuggestion]) -> String {
+    suggestions.iter()
+        .map(|suggestion| format!("{} ({} similar)", suggestion.tag, suggestion.matches))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
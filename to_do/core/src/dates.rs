@@ -0,0 +1,34 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+
+use crate::errors::TaskError;
+
+/// Format used to show dates in listings and detail views (UTC).
+pub const DISPLAY_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Parses a due date given on the command line. All times are UTC.
+///
+/// Accepts RFC 3339 (`2026-10-14T17:00:00Z`), `YYYY-MM-DD HH:MM` and
+/// `YYYY-MM-DD`. A bare date means the end of that day, so a task due today
+/// only becomes overdue once the day is over.
+pub fn parse_due(input: &str) -> Result<DateTime<Utc>, TaskError> {
+    let input = input.trim();
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(input) {
+        return Ok(date_time.with_timezone(&Utc));
+    }
+    if let Ok(date_time) = NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M") {
+        return Ok(date_time.and_utc());
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        let end_of_day = NaiveTime::from_hms_opt(23, 59, 59).unwrap_or_default();
+        return Ok(date.and_time(end_of_day).and_utc());
+    }
+    Err(TaskError::InvalidDate(input.to_string()))
+}
+
+/// Formats an optional date for display, using "-" when it is missing.
+pub fn format_date(date: Option<DateTime<Utc>>) -> String {
+    match date {
+        Some(date) => date.format(DISPLAY_FORMAT).to_string(),
+        None => "-".to_string(),
+    }
+}
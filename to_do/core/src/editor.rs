@@ -0,0 +1,67 @@
+use std::env;
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{self, Command};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::errors::TaskError;
+
+/// Opens `initial` in the user's editor and returns the saved text.
+///
+/// The editor is taken from `VISUAL`, then `EDITOR`, falling back to `vi`,
+/// and run through `sh` so values with arguments (e.g. `code --wait`) work.
+/// The text is kept in a new directory that only the user can open, so
+/// another user can't read it or plant a link in its place.
+///
+/// # Returns
+///
+/// * `Ok(String)` - The text as saved in the editor
+/// * `Err(TaskError::Editor)` - If the editor can't be run or exits with an error
+pub fn edit_text(initial: &str) -> Result<String, TaskError> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let dir = private_dir().map_err(|e| TaskError::Editor(format!("Error creating a temporary directory: {}", e)))?;
+    let path = dir.join("description.md");
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(&path)
+        .and_then(|mut file| file.write_all(initial.as_bytes()))
+        .map_err(|e| TaskError::Editor(format!("Error writing {}: {}", path.display(), e)))?;
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&path)
+        .status();
+    let result = match status {
+        Ok(status) if status.success() => fs::read_to_string(&path)
+            .map_err(|e| TaskError::Editor(format!("Error reading {}: {}", path.display(), e))),
+        Ok(status) => Err(TaskError::Editor(format!("{} exited with {}", editor, status))),
+        Err(e) => Err(TaskError::Editor(format!("Error running {}: {}", editor, e))),
+    };
+    let _ = fs::remove_dir_all(&dir);
+    result
+}
+
+/// Creates a new directory under the temp directory, readable only by the
+/// user. Creating it fails if the name is taken, even by a symlink, so the
+/// next name is tried.
+fn private_dir() -> io::Result<PathBuf> {
+    let mut builder = DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.subsec_nanos());
+    let mut attempt = 0;
+    loop {
+        let dir = env::temp_dir().join(format!("webtodo-{}-{}-{}", process::id(), nanos, attempt));
+        match builder.create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -1,8 +1,11 @@
 use serde::{Serialize, Deserialize};
 use std::fmt;
 
+use crate::errors::TaskError;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum TaskStatus {
     DONE,
     PENDING,
@@ -10,19 +13,51 @@ pub enum TaskStatus {
 
 impl fmt::Display for TaskStatus {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match &self {
-            &Self::DONE => {write!(f, "DONE")},
-            &Self::PENDING => {write!(f, "PENDING")},
+        match self {
+            Self::DONE => {write!(f, "DONE")},
+            Self::PENDING => {write!(f, "PENDING")},
         }
     }
 }
 
 impl TaskStatus {
-    pub fn from_string(status: &String) -> Result<TaskStatus, String> {
+    pub fn from_string(status: &String) -> Result<TaskStatus, TaskError> {
         match status.to_uppercase().as_str() {
             "DONE" => Ok(TaskStatus::DONE),
             "PENDING" => Ok(TaskStatus::PENDING),
-            _ => Err(format!("Invalid status: {}", status))
+            _ => Err(TaskError::InvalidStatus(status.to_string()))
+        }
+    }
+}
+/// How urgent a task is; ordered from `Low` to `Urgent`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Priority {
+    Low,
+    #[default]
+    Medium,
+    High,
+    Urgent,
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Low => write!(f, "LOW"),
+            Self::Medium => write!(f, "MEDIUM"),
+            Self::High => write!(f, "HIGH"),
+            Self::Urgent => write!(f, "URGENT"),
+        }
+    }
+}
+
+impl Priority {
+    pub fn from_string(priority: &str) -> Result<Priority, TaskError> {
+        match priority.to_uppercase().as_str() {
+            "LOW" => Ok(Priority::Low),
+            "MEDIUM" => Ok(Priority::Medium),
+            "HIGH" => Ok(Priority::High),
+            "URGENT" => Ok(Priority::Urgent),
+            _ => Err(TaskError::InvalidPriority(priority.to_string()))
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,131 @@
+use dal::errors::DalError;
+use thiserror::Error;
+
+use crate::enums::TaskStatus;
+
+/// Errors returned by the core task API.
+#[derive(Debug, Error)]
+pub enum TaskError {
+    /// No task exists with the given id.
+    #[error("Task {0} not found")]
+    NotFound(String),
+
+    /// A status string didn't name a known `TaskStatus`.
+    #[error("Invalid status: {0}")]
+    InvalidStatus(String),
+
+    /// A priority string doesn't name a known priority.
+    #[error("Invalid priority: {0} (expected low, medium, high or urgent)")]
+    InvalidPriority(String),
+
+    /// A recurrence string couldn't be parsed.
+    #[error("Invalid recurrence: {0} (expected daily, weekly, monthly, yearly, \"every <n> days|weeks|months|years\" or a cron expression)")]
+    InvalidRecurrence(String),
+
+    /// A `search --regex` pattern isn't a valid regular expression.
+    #[error("Invalid regex: {0}")]
+    InvalidRegex(String),
+
+    /// A task can't be given the requested parent.
+    #[error("{0}")]
+    InvalidParent(String),
+
+    /// Tasks can't be merged as requested.
+    #[error("{0}")]
+    InvalidMerge(String),
+
+    /// A `--sort-by` value doesn't name a sortable field.
+    #[error("Invalid sort key: {0} (expected title, created, due, priority, status or id)")]
+    InvalidSortKey(String),
+
+    /// A `suggest --weights` value isn't a list of `factor=number` pairs.
+    #[error("Invalid weight: {0} (expected due, priority, age or blocking = a number)")]
+    InvalidWeights(String),
+
+    /// A `plan --horizon` value isn't a number of days or weeks.
+    #[error("Invalid horizon: {0} (expected a number of days or weeks, e.g. 7d or 2w)")]
+    InvalidHorizon(String),
+
+    /// The working days or hours set in the environment can't be parsed.
+    #[error("Invalid working hours: {0}")]
+    InvalidWorkingHours(String),
+
+    /// A date string couldn't be parsed.
+    #[error("Invalid date: {0} (expected YYYY-MM-DD, YYYY-MM-DD HH:MM or RFC 3339)")]
+    InvalidDate(String),
+
+    /// A status change isn't allowed from the task's current status.
+    #[error("Task {id} is {from}; it can't be moved to {to}")]
+    InvalidTransition { id: String, from: TaskStatus, to: TaskStatus },
+
+    /// An edit was requested without any field to change.
+    #[error("Nothing to change; pass at least one field to update")]
+    NoChanges,
+
+    /// A destructive command needed confirmation, but there's no terminal to ask on.
+    #[error("Not confirmed: {0} (stdin is not a terminal; pass --force)")]
+    Unconfirmed(String),
+
+    /// Reading an answer from the terminal failed.
+    #[error("Error reading from terminal: {0}")]
+    Prompt(#[source] std::io::Error),
+
+    /// A task couldn't be serialized for output.
+    #[error("Error serializing task: {0}")]
+    Serialize(#[from] serde_json::Error),
+
+    /// The text editor couldn't be run or failed.
+    #[error("{0}")]
+    Editor(String),
+
+    /// At least one `doctor` check failed.
+    #[error("{0} check(s) failed")]
+    Doctor(usize),
+
+    /// A hook script failed or couldn't be run.
+    #[error("{0}")]
+    Hook(String),
+
+    /// The assistant isn't configured, or calling it failed.
+    #[cfg(feature = "assistant")]
+    #[error("{0}")]
+    Assistant(String),
+
+    /// There is nothing to undo or redo, or it can't be done safely.
+    #[error("{0}")]
+    History(String),
+
+    /// The storage layer failed for a reason other than a missing task.
+    #[error(transparent)]
+    Dal(DalError),
+
+    /// More than one step of a command failed, in the order they ran.
+    #[error("{}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    Several(Vec<TaskError>),
+}
+
+impl TaskError {
+    /// Combines the results of steps that all ran, so no failure hides another.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If every step succeeded
+    /// * `Err(TaskError)` - The only error, or `Several` if more than one step failed
+    pub fn combine(results: impl IntoIterator<Item = Result<(), TaskError>>) -> Result<(), TaskError> {
+        let mut errors: Vec<TaskError> = results.into_iter().filter_map(Result::err).collect();
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0)),
+            _ => Err(TaskError::Several(errors)),
+        }
+    }
+}
+
+impl From<DalError> for TaskError {
+    fn from(error: DalError) -> Self {
+        match error {
+            DalError::NotFound(id) => TaskError::NotFound(id),
+            other => TaskError::Dal(other),
+        }
+    }
+}
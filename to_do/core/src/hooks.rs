@@ -0,0 +1,157 @@
+use std::env;
+use std::fmt;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use dal::storage::Storage;
+
+use crate::structs::base::Base;
+use crate::errors::TaskError;
+
+/// Actions that hook scripts can be attached to.
+#[derive(Debug, Clone, Copy)]
+pub enum HookAction {
+    Create,
+    Delete,
+    Done,
+    Reopen,
+}
+
+impl fmt::Display for HookAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HookAction::Create => write!(f, "create"),
+            HookAction::Delete => write!(f, "delete"),
+            HookAction::Done => write!(f, "done"),
+            HookAction::Reopen => write!(f, "reopen"),
+        }
+    }
+}
+
+/// Whether a hook runs before or after the action.
+#[derive(Debug, Clone, Copy)]
+pub enum HookStage {
+    Pre,
+    Post,
+}
+
+impl fmt::Display for HookStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HookStage::Pre => write!(f, "pre"),
+            HookStage::Post => write!(f, "post"),
+        }
+    }
+}
+
+/// Runs the hook configured for `stage` and `action`, if any.
+///
+/// The shell command is read from `TODO_HOOK_<STAGE>_<ACTION>`
+/// (for example `TODO_HOOK_POST_CREATE`) and run with `sh -c`.
+/// The task is passed as JSON on stdin and as the `TODO_TASK_ID`,
+/// `TODO_TASK_TITLE` and `TODO_TASK_STATUS` environment variables; `TODO_HOOK` holds the hook
+/// name (e.g. `post-create`).
+///
+/// A failing pre hook aborts the action. A failing post hook only prints a
+/// warning, because the action has already been persisted. Before a post
+/// hook runs, `store` is flushed, so a hook reading the store sees the change.
+///
+/// # Returns
+///
+/// * `Ok(())` - If no hook is configured or the hook succeeded
+/// * `Err(TaskError::Hook)` - If a pre hook failed or could not be run
+pub fn run(store: &dyn Storage<Base>, stage: HookStage, action: HookAction, task: &Base) -> Result<(), TaskError> {
+    let var = format!("TODO_HOOK_{}_{}", stage, action).to_uppercase();
+    match env::var(&var) {
+        Ok(command) if !command.trim().is_empty() => run_command(store, stage, action, task, &command),
+        _ => Ok(()),
+    }
+}
+
+/// Runs `command` as the hook for `stage` and `action` (see `run`).
+fn run_command(store: &dyn Storage<Base>, stage: HookStage, action: HookAction, task: &Base, command: &str) -> Result<(), TaskError> {
+    let name = format!("{}-{}", stage, action);
+    if let HookStage::Post = stage {
+        store.flush()?;
+    }
+    match execute(&name, command, task) {
+        Ok(()) => Ok(()),
+        Err(e) => match stage {
+            HookStage::Pre => Err(e),
+            HookStage::Post => {
+                eprintln!("warning: {}", e);
+                Ok(())
+            }
+        },
+    }
+}
+
+/// Lists the hooks set in the environment as (variable, command) pairs.
+pub fn configured() -> Vec<(String, String)> {
+    let mut hooks: Vec<(String, String)> = env::vars()
+        .filter(|(var, command)| var.starts_with("TODO_HOOK_") && !command.trim().is_empty())
+        .collect();
+    hooks.sort();
+    hooks
+}
+
+fn execute(name: &str, command: &str, task: &Base) -> Result<(), TaskError> {
+    let payload = serde_json::to_string(task)?;
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("TODO_HOOK", name)
+        .env("TODO_TASK_ID", &task.id)
+        .env("TODO_TASK_TITLE", &task.title)
+        .env("TODO_TASK_STATUS", task.status.to_string())
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| TaskError::Hook(format!("Error running {} hook: {}", name, e)))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that doesn't read stdin closes the pipe early; that's fine.
+        let _ = stdin.write_all(payload.as_bytes());
+    }
+    let status = child.wait().map_err(|e| TaskError::Hook(format!("Error running {} hook: {}", name, e)))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(TaskError::Hook(format!("{} hook failed with {}", name, status)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{store_with, task};
+
+    #[test]
+    fn a_failing_pre_hook_aborts_and_a_failing_post_hook_does_not() {
+        let store = store_with("hooks-failing", Vec::new());
+        let base = task("1", "Buy milk");
+        let pre = run_command(&*store, HookStage::Pre, HookAction::Create, &base, "exit 3");
+        assert!(matches!(pre, Err(TaskError::Hook(message)) if message.contains("pre-create")));
+        run_command(&*store, HookStage::Post, HookAction::Create, &base, "exit 3").unwrap();
+        run_command(&*store, HookStage::Pre, HookAction::Create, &base, "true").unwrap();
+    }
+
+    #[test]
+    fn hooks_are_given_the_task() {
+        let store = store_with("hooks-task", Vec::new());
+        let base = task("1", "Buy milk");
+        let check = r#"test "$TODO_HOOK/$TODO_TASK_ID/$TODO_TASK_TITLE/$TODO_TASK_STATUS" = "pre-done/1/Buy milk/PENDING" && grep -q '"title":"Buy milk"'"#;
+        run_command(&*store, HookStage::Pre, HookAction::Done, &base, check).unwrap();
+        assert!(run_command(&*store, HookStage::Pre, HookAction::Delete, &base, check).is_err());
+    }
+
+    #[test]
+    fn post_hooks_see_the_change_on_disk() {
+        let dir = env::temp_dir().join(format!("webtodo-core-{}-hooks-flushed", std::process::id()));
+        let cached = dal::cached::CachedStore::<Base>::new(store_with("hooks-flushed", Vec::new()));
+        let base = task("1", "Buy milk");
+        cached.save_one("1", &base).unwrap();
+        // A post hook's failure isn't returned, so it leaves a mark instead.
+        let check = format!("cd '{}' && grep -q 'Buy milk' tasks.json && touch seen", dir.display());
+        run_command(&cached, HookStage::Post, HookAction::Create, &base, &check).unwrap();
+        assert!(dir.join("seen").exists());
+    }
+}
@@ -1,23 +1,626 @@
 mod enums;
 mod structs;
 mod api;
-use api::basic_actions::creates::create;
-use crate::enums::TaskStatus;
-use clap::Parser;
+mod normalize;
+mod hooks;
+mod errors;
+mod dates;
+mod editor;
+mod recurrence;
+#[cfg(test)]
+mod testing;
+use api::basic_actions::{
+    creates::{create, Created, NewTask},
+    deletes::delete,
+    edits::{edit, TaskChanges},
+    gets::{get, render_detail, render_json},
+    lists::{list, list_page, render_table, render_tree, ListFilter, Pagination},
+    merges::merge,
+    subtasks::{attach, detach},
+    transitions::{complete, complete_cascade, reopen},
+};
+use api::audit::{annotate, render_history};
+use api::dedupe::{find_duplicates, render_cluster};
+use api::doctor::{diagnose, render_checks, Outcome};
+use api::history::{record, redo, undo};
+use api::plan::{apply, parse_horizon, plan, render_plan, WorkingHours};
+use api::search::{matcher, render_hits, search};
+use api::sorting::{SortKey, SortOrder};
+use api::suggest::{render_suggestions, suggest, Weights};
+use api::store::{info, migrate_legacy, render_info};
+use api::tags::render_tag_suggestions;
+use crate::dates::parse_due;
+use crate::editor::edit_text;
+use crate::enums::{Priority, TaskStatus};
+use crate::errors::TaskError;
+use crate::structs::base::Base;
+use clap::{Parser, Subcommand};
+use dal::oplog::OpLog;
+use dal::storage::Storage;
+use dal::tracked::TrackedStore;
+use std::env;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    #[arg(short, long)]
-    title: String,
-    #[arg(short, long)]
-    status: String,
+    #[command(subcommand)]
+    command: Command,
 }
 
-fn main() -> Result<(), String> {
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Create a new task
+    Create {
+        #[arg(short, long)]
+        title: String,
+        /// Longer notes about the task
+        #[arg(short = 'D', long)]
+        description: Option<String>,
+        #[arg(short, long, default_value = "pending")]
+        status: String,
+        /// low, medium, high or urgent
+        #[arg(short, long, default_value = "medium")]
+        priority: String,
+        /// Due date (YYYY-MM-DD, "YYYY-MM-DD HH:MM" or RFC 3339, in UTC)
+        #[arg(short, long)]
+        due: Option<String>,
+        /// Label the task; repeat for several tags
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Create it as a subtask of this task
+        #[arg(long)]
+        parent: Option<String>,
+        /// Repeat it once done: daily, weekly, monthly, yearly, "every 3 days" or a cron expression
+        #[arg(short, long)]
+        recur: Option<String>,
+        /// Print the tags suggested from tasks with similar titles
+        #[arg(long)]
+        suggest_tags: bool,
+    },
+    /// Show a single task in detail
+    #[command(visible_alias = "get")]
+    Show {
+        id: String,
+        /// Print the stored JSON record instead
+        #[arg(long)]
+        json: bool,
+    },
+    /// Change the title, description, status, priority, due date or tags of a task
+    Edit {
+        id: String,
+        #[arg(short, long)]
+        title: Option<String>,
+        /// Replace the description; an empty value removes it
+        #[arg(short = 'D', long, conflicts_with = "edit_description")]
+        description: Option<String>,
+        /// Write the description in $VISUAL / $EDITOR
+        #[arg(long)]
+        edit_description: bool,
+        /// pending or done, checked and hooked like `done` and `reopen`
+        #[arg(short, long)]
+        status: Option<String>,
+        /// low, medium, high or urgent
+        #[arg(short, long)]
+        priority: Option<String>,
+        /// New due date (YYYY-MM-DD, "YYYY-MM-DD HH:MM" or RFC 3339, in UTC)
+        #[arg(short, long, conflicts_with = "clear_due")]
+        due: Option<String>,
+        /// Remove the due date
+        #[arg(long)]
+        clear_due: bool,
+        /// Make it repeat once done (see `create --recur`)
+        #[arg(short, long, conflicts_with = "clear_recur")]
+        recur: Option<String>,
+        /// Stop it from repeating
+        #[arg(long)]
+        clear_recur: bool,
+        /// Add a tag; repeat for several tags
+        #[arg(long = "tag")]
+        add_tags: Vec<String>,
+        /// Remove a tag; repeat for several tags
+        #[arg(long = "untag")]
+        remove_tags: Vec<String>,
+    },
+    /// Mark a pending task as done
+    Done {
+        id: String,
+        /// Also complete all of its pending subtasks
+        #[arg(long)]
+        cascade: bool,
+    },
+    /// Move a done task back to pending
+    Reopen {
+        id: String,
+    },
+    /// Make a task a subtask of another
+    Attach {
+        id: String,
+        parent: String,
+    },
+    /// Turn a subtask back into a top-level task
+    Detach {
+        id: String,
+    },
+    /// Delete a task
+    Delete {
+        id: String,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Merge duplicates into a task, then delete them
+    Merge {
+        /// The task to keep
+        id: String,
+        /// Tasks to merge into it
+        #[arg(required = true)]
+        duplicates: Vec<String>,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Find open tasks that look like duplicates and merge or delete them
+    Dedupe {
+        /// Merge every cluster into its oldest task without asking
+        #[arg(long)]
+        merge: bool,
+    },
+    /// List tasks as a table
+    List {
+        /// Only show tasks with this status (pending or done)
+        #[arg(short, long)]
+        status: Option<String>,
+        /// Only show tasks with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Show subtasks indented below their parent
+        #[arg(long)]
+        tree: bool,
+        /// Order by title, created, due, priority (default), status or id
+        #[arg(long, default_value = "priority")]
+        sort_by: String,
+        /// Reverse the order
+        #[arg(short, long)]
+        reverse: bool,
+        /// Show at most this many tasks
+        #[arg(short, long, value_parser = parse_positive)]
+        limit: Option<usize>,
+        /// Which page of --limit tasks to show, starting at 1
+        #[arg(long, requires = "limit", default_value_t = 1, value_parser = parse_positive)]
+        page: usize,
+    },
+    /// Find tasks whose title or description contains a text (ignoring case)
+    Search {
+        query: String,
+        /// Treat the query as a regular expression
+        #[arg(long)]
+        regex: bool,
+    },
+    /// Suggest what to work on next, explaining each task's score
+    Suggest {
+        /// How many tasks to suggest
+        #[arg(short, long, default_value_t = 5)]
+        limit: usize,
+        /// Factor weights, e.g. "due=3,priority=2,age=1,blocking=1"
+        #[arg(short, long, default_value = "")]
+        weights: String,
+    },
+    /// Propose due dates for pending tasks that have none
+    Plan {
+        /// How far ahead to plan, e.g. 7d or 2w
+        #[arg(long, default_value = "7d")]
+        horizon: String,
+        /// Most tasks due on one working day, counting ones already due then
+        #[arg(long, default_value_t = 3)]
+        per_day: usize,
+        /// Set the proposed due dates without asking
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Show who changed a task and when
+    History {
+        id: String,
+    },
+    /// Take back the last command that changed tasks
+    Undo,
+    /// Apply the last undone command again
+    Redo,
+    /// Check the configuration, store and hooks, suggesting fixes
+    Doctor,
+    /// Inspect the storage backend
+    Store {
+        #[command(subcommand)]
+        command: StoreCommand,
+    },
+    /// Ask the configured assistant for help with tasks
+    #[cfg(feature = "assistant")]
+    Assistant {
+        /// Print what would be sent, redacted, without sending it
+        #[arg(long, global = true)]
+        dry_run: bool,
+        #[command(subcommand)]
+        command: AssistantCommand,
+    },
+}
+
+#[cfg(feature = "assistant")]
+#[derive(Subcommand, Debug)]
+enum AssistantCommand {
+    /// Summarize a task's description
+    Summarize {
+        id: String,
+    },
+    /// Propose subtasks for a task
+    Breakdown {
+        id: String,
+        /// Create the proposed subtasks
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Draft a review of the past and coming week
+    Review,
+}
+
+#[derive(Subcommand, Debug)]
+enum StoreCommand {
+    /// Show backend diagnostics: location, schema, item counts and durability
+    Info,
+    /// Write a consistent copy of the store, safe while it is in use
+    Snapshot {
+        /// Where to write the copy; must not exist yet for SQLite
+        path: PathBuf,
+    },
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), TaskError> {
     let args = Args::parse();
-    let status_enum = TaskStatus::from_string(&args.status)?;
-    let to_do_item = create(&args.title, status_enum)?;
-    println!("{}", to_do_item);
+    // Doctor opens the store itself, so it can report why opening (or
+    // converting an old store) failed.
+    if let Command::Doctor = args.command {
+        let checks = diagnose();
+        print!("{}", render_checks(&checks));
+        let failed = checks.iter().filter(|check| check.outcome == Outcome::Fail).count();
+        return if failed == 0 { Ok(()) } else { Err(TaskError::Doctor(failed)) };
+    }
+    let converted = migrate_legacy()?;
+    if converted > 0 {
+        eprintln!("Converted {} task(s) from the old title -> status layout", converted);
+    }
+    let store = dal::storage::from_env()?;
+    let recordable = is_recordable(&args.command);
+    let tracked = TrackedStore::new(store.as_ref());
+    let result = dispatch(args.command, &tracked);
+    // Flush on the way out, even after a failed command, so writes the
+    // backend deferred (the cache, STORE_FSYNC=interval) reach the store.
+    let flushed = store.flush().map_err(TaskError::from);
+    // Record even after a failed command: whatever it did change should
+    // still show up in the task history and be undoable. But only once it
+    // is stored, or `undo` would take back something that never happened.
+    let before = tracked.touched();
+    let recorded = if flushed.is_ok() && recordable && !before.is_empty() {
+        annotate(store.as_ref(), &before).and_then(|after| {
+            store.flush()?;
+            let label = env::args().skip(1).collect::<Vec<_>>().join(" ");
+            record(&OpLog::from_env()?, &label, &before, &after)
+        })
+    } else {
+        Ok(())
+    };
+    TaskError::combine([result, flushed, recorded])
+}
+
+/// Whether the changes `command` makes are recorded in the task history and
+/// for `undo`. Undo and redo aren't: they move operations between the
+/// stacks and add their own history entries.
+fn is_recordable(command: &Command) -> bool {
+    !matches!(command, Command::Undo | Command::Redo)
+}
+
+fn dispatch(command: Command, store: &dyn Storage<Base>) -> Result<(), TaskError> {
+    match command {
+        Command::Create { title, description, status, priority, due, tags, parent, recur, suggest_tags: suggest } => {
+            let new_task = NewTask {
+                title,
+                description,
+                status: TaskStatus::from_string(&status)?,
+                priority: Priority::from_string(&priority)?,
+                due: due.as_deref().map(parse_due).transpose()?,
+                tags,
+                parent_id: parent,
+                recurrence: recur,
+                previous_id: None,
+            };
+            let created = create(store, new_task)?;
+            warn_similar(&created);
+            println!("{}", created);
+            if suggest {
+                if created.tag_suggestions.is_empty() {
+                    println!("No tag suggestions");
+                } else {
+                    println!("Suggested tags: {}", render_tag_suggestions(&created.tag_suggestions));
+                    println!("Add one with: edit {} --tag <tag>", created.item.base().id);
+                }
+            }
+        },
+        Command::Show { id, json } => {
+            let item = get(store, &id)?;
+            if json {
+                println!("{}", render_json(&item)?);
+            } else {
+                print!("{}", render_detail(&item));
+            }
+        },
+        Command::Edit { id, title, description, edit_description, status, priority, due, clear_due, recur, clear_recur, add_tags, remove_tags } => {
+            let due = match due {
+                Some(due) => Some(Some(parse_due(&due)?)),
+                None if clear_due => Some(None),
+                None => None,
+            };
+            let description = if edit_description {
+                let current = get(store, &id)?.base().description.clone().unwrap_or_default();
+                Some(edit_text(&current)?)
+            } else {
+                description
+            };
+            let recurrence = match recur {
+                Some(recur) => Some(Some(recur)),
+                None if clear_recur => Some(None),
+                None => None,
+            };
+            let changes = TaskChanges {
+                title,
+                description,
+                status: status.as_ref().map(TaskStatus::from_string).transpose()?,
+                priority: priority.as_deref().map(Priority::from_string).transpose()?,
+                due,
+                recurrence,
+                add_tags,
+                remove_tags,
+            };
+            println!("{}", edit(store, &id, changes)?);
+        },
+        Command::Done { id, cascade: false } => {
+            println!("{}", complete(store, &id)?);
+        },
+        Command::Done { id, cascade: true } => {
+            for item in complete_cascade(store, &id)? {
+                println!("{}", item);
+            }
+        },
+        Command::Attach { id, parent } => {
+            println!("{}", attach(store, &id, &parent)?);
+        },
+        Command::Detach { id } => {
+            println!("{}", detach(store, &id)?);
+        },
+        Command::Reopen { id } => {
+            println!("{}", reopen(store, &id)?);
+        },
+        Command::Delete { id, force } => {
+            let item = get(store, &id)?;
+            if !force && !confirm_destructive(&format!("Delete {}?", item))? {
+                println!("Aborted");
+                return Ok(());
+            }
+            println!("Deleted {}", delete(store, &id)?);
+        },
+        Command::Merge { id, duplicates, force } => {
+            let question = format!("Merge {} task(s) into {} and delete them?", duplicates.len(), get(store, &id)?);
+            if !force && !confirm_destructive(&question)? {
+                println!("Aborted");
+                return Ok(());
+            }
+            println!("Merged into {}", merge(store, &id, &duplicates)?);
+        },
+        Command::Dedupe { merge: merge_all } => {
+            let clusters = find_duplicates(store)?;
+            if clusters.is_empty() {
+                println!("No likely duplicates");
+                return Ok(());
+            }
+            let interactive = !merge_all && io::stdin().is_terminal();
+            for cluster in &clusters {
+                print!("{}", render_cluster(cluster));
+                let answer = if merge_all {
+                    "m".to_string()
+                } else if interactive {
+                    ask("[m]erge into the first, [d]elete the duplicates or [s]kip?")?
+                } else {
+                    continue;
+                };
+                match answer.as_str() {
+                    "m" | "merge" => println!("Merged into {}", merge(store, &cluster.keep.id, &cluster.duplicate_ids())?),
+                    "d" | "delete" => {
+                        for duplicate in &cluster.duplicates {
+                            println!("Deleted {}", delete(store, &duplicate.id)?);
+                        }
+                    },
+                    _ => println!("Skipped"),
+                }
+            }
+            if !merge_all && !interactive {
+                println!("Not changed; run with --merge to merge every cluster into its first task");
+            }
+        },
+        Command::List { status, tag, tree, sort_by, reverse, limit, page } => {
+            let filter = ListFilter {
+                status: status.as_ref().map(TaskStatus::from_string).transpose()?,
+                tag,
+            };
+            let order = SortOrder { key: SortKey::from_string(&sort_by)?, reverse };
+            let items = match limit {
+                Some(limit) => {
+                    let (items, total) = list_page(store, &filter, &order, &Pagination { limit, page })?;
+                    // On stderr, so the table stays easy to parse.
+                    eprintln!("Page {} of {} ({} tasks)", page, total.div_ceil(limit).max(1), total);
+                    items
+                }
+                None => list(store, &filter, &order)?,
+            };
+            if tree {
+                print!("{}", render_tree(&items, use_color()));
+            } else {
+                print!("{}", render_table(&items, use_color()));
+            }
+        },
+        Command::Search { query, regex } => {
+            let hits = search(store, &matcher(&query, regex)?)?;
+            print!("{}", render_hits(&hits, use_color()));
+        },
+        Command::Suggest { limit, weights } => {
+            let suggestions = suggest(store, &Weights::from_string(&weights)?, limit)?;
+            print!("{}", render_suggestions(&suggestions));
+        },
+        Command::Plan { horizon, per_day, apply: apply_now } => {
+            let plan = plan(store, parse_horizon(&horizon)?, per_day, &WorkingHours::from_env()?)?;
+            print!("{}", render_plan(&plan));
+            if plan.proposals.is_empty() {
+                return Ok(());
+            }
+            // Without --apply, only an interactive yes applies the plan.
+            let question = format!("Set {} due date(s)?", plan.proposals.len());
+            let approved = apply_now || (io::stdin().is_terminal() && confirm(&question)?);
+            if !approved {
+                println!("Not applied; run with --apply to set these due dates");
+                return Ok(());
+            }
+            println!("Scheduled {} task(s)", apply(store, &plan)?);
+        },
+        Command::History { id } => {
+            print!("{}", render_history(&get(store, &id)?));
+        },
+        Command::Undo => {
+            println!("Undid: {}", undo(store, &OpLog::from_env()?)?);
+        },
+        Command::Redo => {
+            println!("Redid: {}", redo(store, &OpLog::from_env()?)?);
+        },
+        Command::Doctor => unreachable!("handled before the store is opened"),
+        Command::Store { command: StoreCommand::Info } => {
+            print!("{}", render_info(&info(store)?));
+        },
+        Command::Store { command: StoreCommand::Snapshot { path } } => {
+            store.snapshot(&path)?;
+            println!("Snapshot written to {}", path.display());
+        },
+        #[cfg(feature = "assistant")]
+        Command::Assistant { dry_run, command } => assist(store, dry_run, command)?,
+    }
+    Ok(())
+}
+
+#[cfg(feature = "assistant")]
+fn assist(store: &dyn Storage<Base>, dry_run: bool, command: AssistantCommand) -> Result<(), TaskError> {
+    use api::assistant::{breakdown_prompt, parse_subtasks, review_prompt, summarize_prompt, AssistantConfig};
+
+    let config = AssistantConfig::from_env()?;
+    let prompt = match &command {
+        AssistantCommand::Summarize { id } => summarize_prompt(&config, get(store, id)?.base())?,
+        AssistantCommand::Breakdown { id, .. } => breakdown_prompt(&config, get(store, id)?.base()),
+        AssistantCommand::Review => review_prompt(&config, &store.get_all()?, chrono::Utc::now()),
+    };
+    if dry_run {
+        println!("Would send to {} ({}):\n\n{}\n\n{}", config.url, config.model, prompt.system, prompt.user);
+        return Ok(());
+    }
+    let reply = config.ask(&prompt)?;
+    match command {
+        AssistantCommand::Breakdown { id, apply } => {
+            let titles = parse_subtasks(&reply);
+            for title in &titles {
+                println!("- {}", title);
+            }
+            if apply {
+                for title in titles {
+                    let new_task = NewTask {
+                        title,
+                        description: None,
+                        status: TaskStatus::PENDING,
+                        priority: Priority::default(),
+                        due: None,
+                        tags: Vec::new(),
+                        parent_id: Some(id.clone()),
+                        recurrence: None,
+                        previous_id: None,
+                    };
+                    let created = create(store, new_task)?;
+                    warn_similar(&created);
+                    println!("{}", created);
+                }
+            }
+        },
+        _ => println!("{}", reply),
+    }
     Ok(())
 }
+
+/// Warns that a new task's title matches a stored one's.
+fn warn_similar(created: &Created) {
+    if let Some(similar) = &created.similar {
+        eprintln!("warning: task {} has a similar title: {}", similar.id, similar.title);
+    }
+}
+
+/// Parses a count that must be at least 1, for `--limit` and `--page`.
+fn parse_positive(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(n) => Ok(n),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Colours output only on a terminal, and never when `NO_COLOR` is set.
+fn use_color() -> bool {
+    io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+/// Asks a yes/no question on the terminal; anything but "y"/"yes" is a no.
+fn confirm(question: &str) -> Result<bool, TaskError> {
+    Ok(matches!(ask(&format!("{} [y/N]", question))?.as_str(), "y" | "yes"))
+}
+
+/// Asks before something that can't be undone from the terminal. Without
+/// one there's nobody to ask, so it's refused rather than done silently.
+fn confirm_destructive(question: &str) -> Result<bool, TaskError> {
+    if !io::stdin().is_terminal() {
+        return Err(TaskError::Unconfirmed(question.to_string()));
+    }
+    confirm(question)
+}
+
+/// Asks a question on the terminal and returns the answer, trimmed and lowercase.
+fn ask(question: &str) -> Result<String, TaskError> {
+    print!("{} ", question);
+    io::stdout().flush().map_err(TaskError::Prompt)?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer).map_err(TaskError::Prompt)?;
+    Ok(answer.trim().to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_and_limits_start_at_one() {
+        let parse = |args: &[&str]| Args::try_parse_from(["core", "list"].iter().chain(args));
+        assert!(parse(&["--limit", "0"]).is_err());
+        assert!(parse(&["--limit", "5", "--page", "0"]).is_err());
+        assert!(parse(&["--page", "2"]).is_err());
+        let Command::List { limit, page, .. } = parse(&["--limit", "5", "--page", "2"]).unwrap().command else {
+            panic!("not parsed as list");
+        };
+        assert_eq!((limit, page), (Some(5), 2));
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::HashSet;
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+/// Normalizes a title for storage and display.
+///
+/// Applies Unicode NFC composition, trims surrounding whitespace and
+/// collapses inner runs of whitespace into a single space, so that the
+/// same visible title is always stored with the same bytes.
+pub fn normalize_title(title: &str) -> String {
+    title.nfc().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Normalizes a tag: like a title, but lowercase and with inner
+/// whitespace replaced by dashes, so `Home Office` is stored as `home-office`.
+pub fn normalize_tag(tag: &str) -> String {
+    normalize_title(tag).to_lowercase().replace(' ', "-")
+}
+
+/// Normalizes a free-form description: NFC, trailing whitespace removed
+/// from each line and from the end. Blank descriptions become `None`.
+pub fn normalize_description(description: &str) -> Option<String> {
+    let description = description.nfc().collect::<String>();
+    let lines: Vec<&str> = description.lines().map(str::trim_end).collect();
+    let description = lines.join("\n").trim().to_string();
+    if description.is_empty() { None } else { Some(description) }
+}
+
+/// Builds the key used to compare titles with each other.
+///
+/// On top of `normalize_title` it strips accents and casefolds, so that
+/// "Café", "CAFE" and "cafe" all share the key "cafe", and "Straße" and
+/// "STRASSE" the key "strasse". Use it for
+/// matching, searching, duplicate detection and sorting; never store it.
+pub fn match_key(title: &str) -> String {
+    fold(&normalize_title(title))
+}
+
+/// Strips accents and casefolds `text` the way `match_key` does, without
+/// touching its whitespace.
+pub fn fold(text: &str) -> String {
+    case_fold(&strip_accents(text))
+}
+
+/// Full case folding, where one character can fold to several: "ß" and
+/// "ẞ" become "ss", "ﬁ" becomes "fi" and a final "ς" becomes "σ".
+///
+/// Lowercasing what uppercasing the lowercase gives reaches the same
+/// result as Unicode's case folding table, except that a Turkish dotless
+/// "ı" folds to "i" as well.
+fn case_fold(text: &str) -> String {
+    text.chars()
+        .flat_map(char::to_lowercase)
+        .flat_map(char::to_uppercase)
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Drops the accents of Latin, Greek and Cyrillic letters, so "Café"
+/// becomes "Cafe". Marks on letters of other scripts are part of how those
+/// are spelled (a Devanagari vowel sign, say) and are kept.
+pub fn strip_accents(text: &str) -> String {
+    let mut accented = false;
+    let stripped: String = text.nfd()
+        .filter(|&c| {
+            if !is_combining_mark(c) {
+                accented = has_accents(c);
+                return true;
+            }
+            !accented
+        })
+        .collect();
+    stripped.nfc().collect()
+}
+
+/// Whether `c` is a letter of a script whose combining marks are accents:
+/// Latin, Greek or Cyrillic.
+fn has_accents(c: char) -> bool {
+    matches!(c as u32,
+        0x0041..=0x024F // Basic Latin to Latin Extended-B
+        | 0x0250..=0x02AF // IPA
+        | 0x0370..=0x03FF // Greek
+        | 0x0400..=0x052F // Cyrillic and its supplement
+        | 0x1C80..=0x1C8F // Cyrillic Extended-C
+        | 0x1E00..=0x1FFF // Latin Extended Additional, Greek Extended
+        | 0x2C60..=0x2C7F // Latin Extended-C
+        | 0xA640..=0xA69F // Cyrillic Extended-B
+        | 0xA720..=0xA7FF // Latin Extended-D
+        | 0xAB30..=0xAB6F // Latin Extended-E
+        | 0xFF21..=0xFF5A // Fullwidth Latin
+    )
+}
+
+/// The three-character windows of the title's match key, padded so that
+/// the starts and ends of words count too.
+pub fn trigrams(title: &str) -> HashSet<[char; 3]> {
+    let mut set = HashSet::new();
+    for word in match_key(title).split_whitespace() {
+        let chars: Vec<char> = format!("  {} ", word).chars().collect();
+        set.extend(chars.windows(3).map(|w| [w[0], w[1], w[2]]));
+    }
+    set
+}
+
+/// Jaccard similarity of two trigram sets, from 0 (nothing shared) to 1.
+pub fn jaccard(a: &HashSet<[char; 3]>, b: &HashSet<[char; 3]>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_ignore_case_accents_and_spacing() {
+        assert_eq!(match_key("  Café   au lait "), "cafe au lait");
+        assert_eq!(match_key("CAFE AU LAIT"), "cafe au lait");
+        assert_eq!(match_key("Crème brûlée"), match_key("CREME BRULEE"));
+        assert_eq!(match_key("Ёлка"), match_key("елка"));
+        assert_eq!(match_key("Ἀθῆναι"), match_key("ΑΘΗΝΑΙ"));
+    }
+
+    #[test]
+    fn case_folding_can_change_the_length() {
+        assert_eq!(match_key("Straße"), "strasse");
+        assert_eq!(match_key("STRASSE"), match_key("Straße"));
+        assert_eq!(match_key("GROẞ"), "gross");
+        assert_eq!(match_key("ﬁle"), "file");
+        assert_eq!(match_key("ΟΔΟΣ"), match_key("οδος"));
+    }
+
+    #[test]
+    fn marks_of_other_scripts_are_kept() {
+        // किताब (book) and कताब differ only in the vowel sign.
+        assert_eq!(strip_accents("किताब"), "किताब");
+        assert_ne!(match_key("किताब"), match_key("कताब"));
+        assert_eq!(strip_accents("한국어"), "한국어");
+        assert_eq!(strip_accents("naïve café"), "naive cafe");
+    }
+}
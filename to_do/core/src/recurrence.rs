@@ -0,0 +1,283 @@
+use chrono::{DateTime, Datelike, Duration, Months, TimeZone, Timelike, Utc};
+
+use crate::errors::TaskError;
+
+/// A calendar unit for interval recurrences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+/// When a recurring task comes back after being completed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recurrence {
+    /// Every `count` units after the previous due date.
+    Every { count: u32, unit: Unit },
+    /// At the times matched by a five-field cron expression (UTC).
+    Cron(Cron),
+}
+
+impl Recurrence {
+    /// Parses `daily`, `weekly`, `monthly`, `yearly`, `every <n> <unit>s`
+    /// (e.g. `every 3 days`, `every week`) or a cron expression such as
+    /// `0 9 * * 1-5`.
+    pub fn parse(input: &str) -> Result<Recurrence, TaskError> {
+        let text = input.trim().to_lowercase();
+        let invalid = || TaskError::InvalidRecurrence(input.to_string());
+        let every = |count, unit| Ok(Recurrence::Every { count, unit });
+        match text.as_str() {
+            "daily" => return every(1, Unit::Day),
+            "weekly" => return every(1, Unit::Week),
+            "monthly" => return every(1, Unit::Month),
+            "yearly" | "annually" => return every(1, Unit::Year),
+            _ => {}
+        }
+        if let Some(rest) = text.strip_prefix("every ") {
+            let words: Vec<&str> = rest.split_whitespace().collect();
+            let (count, unit) = match words.as_slice() {
+                [unit] => (1, *unit),
+                [count, unit] => (count.parse::<u32>().map_err(|_| invalid())?, *unit),
+                _ => return Err(invalid()),
+            };
+            if count == 0 {
+                return Err(invalid());
+            }
+            let unit = match unit.trim_end_matches('s') {
+                "day" => Unit::Day,
+                "week" => Unit::Week,
+                "month" => Unit::Month,
+                "year" => Unit::Year,
+                _ => return Err(invalid()),
+            };
+            return every(count, unit);
+        }
+        Cron::parse(&text).map(Recurrence::Cron).ok_or_else(invalid)
+    }
+
+    /// Returns the first occurrence after both `previous` (the last due date,
+    /// if any) and `now`, so occurrences missed while the task was open are
+    /// skipped rather than piling up.
+    pub fn next_after(&self, previous: Option<DateTime<Utc>>, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Recurrence::Every { count, unit } => {
+                let start = previous.unwrap_or(now);
+                // Step from the start each time, so monthly tasks due on the
+                // 31st come back on the last day of shorter months, not the 28th.
+                for step in 1u32.. {
+                    let n = count.checked_mul(step)?;
+                    let next = match unit {
+                        Unit::Day => start.checked_add_signed(Duration::days(i64::from(n)))?,
+                        Unit::Week => start.checked_add_signed(Duration::weeks(i64::from(n)))?,
+                        Unit::Month => start.checked_add_months(Months::new(n))?,
+                        Unit::Year => start.checked_add_months(Months::new(n.checked_mul(12)?))?,
+                    };
+                    if next > now {
+                        return Some(next);
+                    }
+                }
+                None
+            }
+            Recurrence::Cron(cron) => cron.next_after(previous.map_or(now, |previous| previous.max(now))),
+        }
+    }
+}
+
+/// A parsed five-field cron expression: minute, hour, day of month, month
+/// and day of week (0 or 7 is Sunday). Fields accept `*`, numbers, ranges
+/// (`1-5`), lists (`1,15`) and steps (`*/15`, `0-30/10`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days: Vec<u32>,
+    months: Vec<u32>,
+    weekdays: Vec<u32>,
+    /// Whether the day-of-month and day-of-week fields were restricted
+    /// (don't start with `*`); when both are, a day matching either one
+    /// matches, otherwise it must match both (as in cron).
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl Cron {
+    fn parse(text: &str) -> Option<Cron> {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields.as_slice() else {
+            return None;
+        };
+        let mut weekdays = parse_field(weekday, 0, 7)?;
+        // Both 0 and 7 mean Sunday.
+        for w in weekdays.iter_mut() {
+            if *w == 7 {
+                *w = 0;
+            }
+        }
+        Some(Cron {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days: parse_field(day, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            weekdays,
+            days_restricted: !day.starts_with('*'),
+            weekdays_restricted: !weekday.starts_with('*'),
+        })
+    }
+
+    fn day_matches(&self, date: DateTime<Utc>) -> bool {
+        let day = self.days.contains(&date.day());
+        let weekday = self.weekdays.contains(&date.weekday().num_days_from_sunday());
+        if self.days_restricted && self.weekdays_restricted { day || weekday } else { day && weekday }
+    }
+
+    /// Returns the first matching minute strictly after `after`, looking at
+    /// most five years ahead.
+    fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = start + Duration::days(5 * 366);
+        let mut t = start;
+        while t < limit {
+            if !self.months.contains(&t.month()) || !self.day_matches(t) {
+                // Skip to the next midnight.
+                let date = t.date_naive().succ_opt()?;
+                t = Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0)?);
+                continue;
+            }
+            if !self.hours.contains(&t.hour()) {
+                t = t.with_minute(0)? + Duration::hours(1);
+                continue;
+            }
+            if self.minutes.contains(&t.minute()) {
+                return Some(t);
+            }
+            t += Duration::minutes(1);
+        }
+        None
+    }
+}
+
+/// Expands one cron field into the sorted values it matches.
+fn parse_field(field: &str, min: u32, max: u32) -> Option<Vec<u32>> {
+    let mut values = Vec::new();
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|step| *step > 0)?),
+            None => (part, 1),
+        };
+        let (low, high) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((low, high)) => (low.parse().ok()?, high.parse().ok()?),
+                None => {
+                    let value = range.parse().ok()?;
+                    // `5/10` means "from 5 to the end, every 10".
+                    (value, if step > 1 { max } else { value })
+                }
+            },
+        };
+        if low < min || high > max || low > high {
+            return None;
+        }
+        values.extend((low..=high).step_by(step as usize));
+    }
+    values.sort_unstable();
+    values.dedup();
+    Some(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text).unwrap().with_timezone(&Utc)
+    }
+
+    fn next_cron(expression: &str, after: &str) -> Option<DateTime<Utc>> {
+        Recurrence::parse(expression).unwrap().next_after(None, at(after))
+    }
+
+    #[test]
+    fn parses_names_and_intervals() {
+        assert_eq!(Recurrence::parse("Daily").unwrap(), Recurrence::Every { count: 1, unit: Unit::Day });
+        assert_eq!(Recurrence::parse("annually").unwrap(), Recurrence::Every { count: 1, unit: Unit::Year });
+        assert_eq!(Recurrence::parse("every week").unwrap(), Recurrence::Every { count: 1, unit: Unit::Week });
+        assert_eq!(Recurrence::parse(" every 3 months ").unwrap(), Recurrence::Every { count: 3, unit: Unit::Month });
+    }
+
+    #[test]
+    fn rejects_invalid_recurrences() {
+        for input in [
+            "", "sometimes", "every", "every 0 days", "every -1 days", "every 3 fortnights", "every 1 2 days",
+            "* * * *", "* * * * * *", "60 * * * *", "* 24 * * *", "* * 0 * *", "* * * 13 *", "* * * * 8",
+            "5-1 * * * *", "*/0 * * * *", "1,,2 * * * *", "a * * * *",
+        ] {
+            assert!(Recurrence::parse(input).is_err(), "{:?} was accepted", input);
+        }
+    }
+
+    #[test]
+    fn intervals_skip_missed_occurrences() {
+        let daily = Recurrence::parse("daily").unwrap();
+        let next = daily.next_after(Some(at("2026-10-01T09:00:00Z")), at("2026-10-14T12:00:00Z"));
+        assert_eq!(next, Some(at("2026-10-15T09:00:00Z")));
+        let next = daily.next_after(None, at("2026-10-14T12:00:00Z"));
+        assert_eq!(next, Some(at("2026-10-15T12:00:00Z")));
+    }
+
+    #[test]
+    fn monthly_keeps_the_end_of_the_month() {
+        let monthly = Recurrence::parse("monthly").unwrap();
+        let start = at("2027-01-31T09:00:00Z");
+        assert_eq!(monthly.next_after(Some(start), start), Some(at("2027-02-28T09:00:00Z")));
+        assert_eq!(monthly.next_after(Some(start), at("2027-03-01T00:00:00Z")), Some(at("2027-03-31T09:00:00Z")));
+        let yearly = Recurrence::parse("yearly").unwrap();
+        let leap = at("2028-02-29T00:00:00Z");
+        assert_eq!(yearly.next_after(Some(leap), leap), Some(at("2029-02-28T00:00:00Z")));
+    }
+
+    #[test]
+    fn cron_is_strictly_after() {
+        assert_eq!(next_cron("0 9 * * *", "2026-10-14T09:00:00Z"), Some(at("2026-10-15T09:00:00Z")));
+        assert_eq!(next_cron("0 9 * * *", "2026-10-14T08:59:59Z"), Some(at("2026-10-14T09:00:00Z")));
+        assert_eq!(next_cron("*/15 * * * *", "2026-10-14T23:50:00Z"), Some(at("2026-10-15T00:00:00Z")));
+    }
+
+    #[test]
+    fn cron_skips_weekends_and_wraps_years() {
+        // 2026-10-16 is a Friday.
+        assert_eq!(next_cron("0 9 * * 1-5", "2026-10-16T09:00:00Z"), Some(at("2026-10-19T09:00:00Z")));
+        assert_eq!(next_cron("30 23 31 12 *", "2026-12-31T23:30:00Z"), Some(at("2027-12-31T23:30:00Z")));
+    }
+
+    #[test]
+    fn cron_sunday_is_0_or_7() {
+        assert_eq!(Recurrence::parse("0 0 * * 0").unwrap(), Recurrence::parse("0 0 * * 7").unwrap());
+        // 2026-10-18 is a Sunday.
+        assert_eq!(next_cron("0 0 * * 7", "2026-10-14T00:00:00Z"), Some(at("2026-10-18T00:00:00Z")));
+    }
+
+    #[test]
+    fn cron_finds_leap_days_and_gives_up_on_impossible_dates() {
+        assert_eq!(next_cron("0 0 29 2 *", "2026-03-01T00:00:00Z"), Some(at("2028-02-29T00:00:00Z")));
+        assert_eq!(next_cron("0 0 31 2 *", "2026-01-01T00:00:00Z"), None);
+    }
+
+    #[test]
+    fn cron_day_fields_combine_like_cron() {
+        // Both restricted: either matches. 2026-10-02 is the first Friday.
+        assert_eq!(next_cron("0 0 13 * 5", "2026-10-01T00:00:00Z"), Some(at("2026-10-02T00:00:00Z")));
+        // A field starting with `*` isn't restricted, so both must match:
+        // the first Monday on an odd day after the 6th is the 19th.
+        assert_eq!(next_cron("0 0 */2 * 1", "2026-10-06T00:00:00Z"), Some(at("2026-10-19T00:00:00Z")));
+    }
+
+    #[test]
+    fn cron_steps_and_lists() {
+        assert_eq!(parse_field("5/20", 0, 59), Some(vec![5, 25, 45]));
+        assert_eq!(parse_field("0-30/10,45", 0, 59), Some(vec![0, 10, 20, 30, 45]));
+        assert_eq!(parse_field("3,1,3", 0, 59), Some(vec![1, 3]));
+    }
+}
@@ -1,6 +1,77 @@
-use super::super::enums::TaskStatus;
+use chrono::{DateTime, Utc};
+use dal::storage::Tagged;
+use serde::{Serialize, Deserialize};
+use super::super::enums::{Priority, TaskStatus};
+use crate::normalize::normalize_tag;
 
+/// The persisted form of a task, stored under its `id`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Base {
+    pub id: String,
     pub title: String,
+    /// Free-form, possibly multi-line notes.
+    #[serde(default)]
+    pub description: Option<String>,
     pub status: TaskStatus,
-}
\ No newline at end of file
+    /// When the task was created; `None` for tasks stored before timestamps existed.
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    /// Tasks stored before priorities existed are `Medium`.
+    #[serde(default)]
+    pub priority: Priority,
+    /// When the task is due, if it has a deadline.
+    #[serde(default)]
+    pub due: Option<DateTime<Utc>>,
+    /// The task this one is a subtask of, if any.
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    /// How the task repeats once done (see `Recurrence::parse`).
+    #[serde(default)]
+    pub recurrence: Option<String>,
+    /// The recurring task this one was created as the next instance of.
+    #[serde(default)]
+    pub previous_id: Option<String>,
+    /// Normalized labels (see `normalize_tag`), without duplicates.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Every change made to the task, oldest first.
+    #[serde(default)]
+    pub history: Vec<HistoryEntry>,
+}
+
+/// One change to a task: when, by whom and what changed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub at: DateTime<Utc>,
+    /// The user who made the change (`TODO_USER`, else `USER`).
+    pub by: String,
+    /// A short description, e.g. `status PENDING -> DONE`.
+    pub change: String,
+}
+
+impl Base {
+    /// Returns true if the task is still pending and its due date has passed.
+    pub fn is_overdue(&self, now: DateTime<Utc>) -> bool {
+        self.status == TaskStatus::PENDING && self.due.is_some_and(|due| due < now)
+    }
+
+    /// Adds `tag` after normalizing it, unless the task already has it.
+    pub fn add_tag(&mut self, tag: &str) {
+        let tag = normalize_tag(tag);
+        if !tag.is_empty() && !self.tags.contains(&tag) {
+            self.tags.push(tag);
+        }
+    }
+
+    /// Removes `tag`, matched after normalizing it.
+    pub fn remove_tag(&mut self, tag: &str) {
+        let tag = normalize_tag(tag);
+        self.tags.retain(|t| t != &tag);
+    }
+}
+
+impl Tagged for Base {
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+}
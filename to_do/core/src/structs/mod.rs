@@ -1,3 +1,3 @@
-mod base;
+pub mod base;
 pub mod done;
 pub mod pending;
\ No newline at end of file
@@ -1,16 +1,27 @@
+use chrono::Utc;
 use super::base::Base;
-use super::super::enums::TaskStatus;
+use super::super::enums::{Priority, TaskStatus};
 
 pub struct Pending {
     pub super_struct: Base,
 }
 
 impl Pending {
-    pub fn new(input_title: &str) -> Self {
+    pub fn new(id: &str, input_title: &str) -> Self {
         let base = Base {
+            id: id.to_string(),
             title: input_title.to_string(),
+            description: None,
             status: TaskStatus::PENDING,
+            created_at: Some(Utc::now()),
+            priority: Priority::default(),
+            due: None,
+            parent_id: None,
+            recurrence: None,
+            previous_id: None,
+            tags: Vec::new(),
+            history: Vec::new(),
         };
-        return Pending{super_struct: base}
+        Pending{super_struct: base}
     }
 }
\ No newline at end of file
@@ -0,0 +1,25 @@
+//! Helpers shared by the unit tests.
+
+use dal::json_file::JsonFileStore;
+use dal::storage::Storage;
+use std::{env, fs, process};
+
+use crate::structs::{base::Base, pending::Pending};
+
+/// A JSON store in a fresh directory under the temp directory, named after
+/// the test, holding `tasks` under their ids.
+pub fn store_with(name: &str, tasks: Vec<Base>) -> Box<dyn Storage<Base>> {
+    let dir = env::temp_dir().join(format!("webtodo-core-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let store = JsonFileStore::new(dir.join("tasks.json"));
+    for task in tasks {
+        store.save_one(&task.id.clone(), &task).unwrap();
+    }
+    Box::new(store)
+}
+
+/// A pending task with `id` and `title` and nothing else set.
+pub fn task(id: &str, title: &str) -> Base {
+    Pending::new(id, title).super_struct
+}
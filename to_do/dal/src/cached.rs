@@ -0,0 +1,277 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::errors::DalError;
+use crate::storage::{apply_to, page_of, Page, Storage, Tagged};
+
+/// Keeps the items of another backend in memory, so repeated reads during
+/// one process don't go back to the disk or database.
+///
+/// The items are loaded on first use. Writes only change the cache and
+/// remember which ids they touched; `flush` writes those entries to the
+/// wrapped backend in one batch (see `Storage::apply_changes`), so entries
+/// changed by other processes in the meantime are left alone. Dropping the
+/// store flushes it too, but ignores errors, so callers should call `flush`
+/// themselves before exiting.
+///
+/// `insert_new` is the exception: it writes through at once, so a taken id
+/// is noticed even if another process took it after the items were loaded.
+///
+/// Pages and tag queries go straight to the wrapped backend, which can
+/// answer them without loading everything, as long as there are no
+/// unflushed writes they would miss.
+///
+/// Writes made by other processes after the items were loaded aren't seen
+/// until `reload` is called.
+///
+/// # Type Parameters
+///
+/// * `T` - Type of the stored items
+pub struct CachedStore<T: Clone> {
+    inner: Box<dyn Storage<T>>,
+    state: Mutex<CacheState<T>>,
+}
+
+struct CacheState<T> {
+    /// `None` until the items are first needed.
+    items: Option<HashMap<String, T>>,
+    /// Ids saved or deleted since the last flush.
+    dirty: HashSet<String>,
+    /// `save_all` replaced everything, so the next flush does the same.
+    replaced: bool,
+}
+
+impl<T: Clone> CachedStore<T> {
+    /// Wraps `inner`; nothing is read until the first operation.
+    pub fn new(inner: Box<dyn Storage<T>>) -> Self {
+        CachedStore {
+            inner,
+            state: Mutex::new(CacheState { items: None, dirty: HashSet::new(), replaced: false }),
+        }
+    }
+
+    /// Drops the cached items, so the next read loads them again. Changes
+    /// that weren't flushed yet are written first.
+    pub fn reload(&self) -> Result<(), DalError> {
+        self.flush()?;
+        self.lock().items = None;
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CacheState<T>> {
+        // A panic while holding the lock can't leave the map half-updated
+        // (every update is a single insert or remove), so keep using it.
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Whether nothing was written since the last flush, so the wrapped
+    /// backend holds what the cache does.
+    fn is_clean(&self) -> bool {
+        let state = self.lock();
+        state.dirty.is_empty() && !state.replaced
+    }
+
+    /// Runs `f` on the cached items, loading them first if needed.
+    fn with_items<R>(&self, f: impl FnOnce(&mut CacheState<T>, &mut HashMap<String, T>) -> R) -> Result<R, DalError> {
+        let mut state = self.lock();
+        let mut items = match state.items.take() {
+            Some(items) => items,
+            None => self.inner.get_all()?,
+        };
+        let result = f(&mut state, &mut items);
+        state.items = Some(items);
+        Ok(result)
+    }
+}
+
+impl<T: Clone> Storage<T> for CachedStore<T> {
+    fn get_all(&self) -> Result<HashMap<String, T>, DalError> {
+        self.with_items(|_, items| items.clone())
+    }
+
+    fn save_all(&self, tasks: &HashMap<String, T>) -> Result<(), DalError> {
+        let mut state = self.lock();
+        state.items = Some(tasks.clone());
+        state.dirty.clear();
+        state.replaced = true;
+        Ok(())
+    }
+
+    fn get_one(&self, id: &str) -> Result<T, DalError> {
+        self.with_items(|_, items| items.get(id).cloned())?
+            .ok_or_else(|| DalError::NotFound(id.to_string()))
+    }
+
+    fn save_one(&self, id: &str, task: &T) -> Result<(), DalError> {
+        self.with_items(|state, items| {
+            items.insert(id.to_string(), task.clone());
+            state.dirty.insert(id.to_string());
+        })
+    }
+
+    /// Inserts straight into the wrapped backend, since only it can tell
+    /// whether another process took the id since the items were loaded.
+    fn insert_new(&self, id: &str, task: &T) -> Result<(), DalError> {
+        self.with_items(|_, items| {
+            if items.contains_key(id) {
+                return Err(DalError::AlreadyExists(id.to_string()));
+            }
+            self.inner.insert_new(id, task)?;
+            items.insert(id.to_string(), task.clone());
+            Ok(())
+        })?
+    }
+
+    fn delete_one(&self, id: &str) -> Result<(), DalError> {
+        self.with_items(|state, items| {
+            items.remove(id);
+            state.dirty.insert(id.to_string());
+        })
+    }
+
+    fn apply_changes(&self, changes: &[(String, Option<T>)]) -> Result<(), DalError> {
+        self.with_items(|state, items| {
+            apply_to(items, changes);
+            state.dirty.extend(changes.iter().map(|(id, _)| id.clone()));
+        })
+    }
+
+    fn get_page(&self, offset: usize, limit: usize) -> Result<Page<T>, DalError> {
+        if self.is_clean() {
+            return self.inner.get_page(offset, limit);
+        }
+        Ok(page_of(self.get_all()?, offset, limit))
+    }
+
+    fn get_by_tag(&self, tag: &str) -> Result<HashMap<String, T>, DalError> where T: Tagged {
+        if self.is_clean() {
+            return self.inner.get_by_tag(tag);
+        }
+        self.with_items(|_, items| {
+            items.iter()
+                .filter(|(_, task)| task.tags().iter().any(|t| t == tag))
+                .map(|(id, task)| (id.clone(), task.clone()))
+                .collect()
+        })
+    }
+
+    /// Writes the changed entries to the wrapped backend in one batch and
+    /// flushes it.
+    ///
+    /// If the write fails, every entry stays dirty, so a later flush retries
+    /// them all.
+    fn flush(&self) -> Result<(), DalError> {
+        let mut guard = self.lock();
+        let state = &mut *guard;
+        if let Some(items) = &state.items {
+            if state.replaced {
+                self.inner.save_all(items)?;
+            } else if !state.dirty.is_empty() {
+                let mut changes: Vec<(String, Option<T>)> = state.dirty.iter()
+                    .map(|id| (id.clone(), items.get(id).cloned()))
+                    .collect();
+                changes.sort_by(|a, b| a.0.cmp(&b.0));
+                self.inner.apply_changes(&changes)?;
+            }
+            state.replaced = false;
+            state.dirty.clear();
+        }
+        drop(guard);
+        self.inner.flush()
+    }
+
+    fn durability(&self) -> String {
+        format!(
+            "writes are held in memory until the command exits, then {}",
+            self.inner.durability()
+        )
+    }
+
+    fn snapshot(&self, dest: &Path) -> Result<(), DalError> {
+        // Flush first, so the copy includes this process' own writes.
+        self.flush()?;
+        self.inner.snapshot(dest)
+    }
+
+    fn diagnostics(&self) -> Result<Vec<(&'static str, String)>, DalError> {
+        let mut details = self.inner.diagnostics()?;
+        let state = self.lock();
+        let cache = match &state.items {
+            Some(items) => format!("on ({} item(s) loaded, {} unflushed change(s))", items.len(), state.dirty.len()),
+            None => "on (nothing loaded yet)".to_string(),
+        };
+        details.push(("Cache", cache));
+        Ok(details)
+    }
+}
+
+impl<T: Clone> Drop for CachedStore<T> {
+    fn drop(&mut self) {
+        // There's no one to report an error to here; `flush` is the way to
+        // find out whether the writes made it.
+        let _ = self.flush();
+    }
+}
+
+/// Reads the `STORE_CACHE` environment variable: `on` or `off`.
+/// If not defined, the cache is on.
+///
+/// # Returns
+///
+/// * `Ok(bool)` - Whether backends should be wrapped in a `CachedStore`
+/// * `Err(DalError)` - `Config` if the value is neither `on` nor `off`
+pub fn enabled_from_env() -> Result<bool, DalError> {
+    match env::var("STORE_CACHE") {
+        Ok(value) => match value.to_lowercase().as_str() {
+            "on" => Ok(true),
+            "off" => Ok(false),
+            _ => Err(DalError::Config(format!("Invalid cache setting: {}", value)))
+        },
+        Err(_) => Ok(true),
+    }
+}
+
+#[cfg(all(test, feature = "json-file"))]
+mod tests {
+    use super::*;
+    use crate::json_file::JsonFileStore;
+    use serde_json::{json, Value};
+    use std::fs;
+    use std::process;
+
+    fn temp_store(name: &str) -> JsonFileStore {
+        let dir = env::temp_dir().join(format!("webtodo-cached-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        JsonFileStore::new(dir.join("tasks.json"))
+    }
+
+    #[test]
+    fn writes_reach_the_backend_on_flush() {
+        let backend = temp_store("flush");
+        let path = backend.path.clone();
+        backend.save_one("gone", &json!("old")).unwrap();
+        let cached = CachedStore::<Value>::new(Box::new(backend));
+        cached.save_one("1", &json!("one")).unwrap();
+        cached.save_one("2", &json!("two")).unwrap();
+        cached.delete_one("gone").unwrap();
+        let on_disk = || Storage::<Value>::get_all(&JsonFileStore::new(&path)).unwrap();
+        assert_eq!(on_disk().len(), 1);
+        cached.flush().unwrap();
+        assert_eq!(on_disk(), HashMap::from([("1".to_string(), json!("one")), ("2".to_string(), json!("two"))]));
+    }
+
+    #[test]
+    fn pages_see_unflushed_writes() {
+        let backend = temp_store("pages");
+        backend.save_one("1", &json!("one")).unwrap();
+        let cached = CachedStore::<Value>::new(Box::new(backend));
+        assert_eq!(cached.get_page(0, 10).unwrap().total, 1);
+        cached.save_one("2", &json!("two")).unwrap();
+        let page = cached.get_page(1, 10).unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(page.items, vec![("2".to_string(), json!("two"))]);
+    }
+}
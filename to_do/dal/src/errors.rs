@@ -0,0 +1,54 @@
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Errors returned by the storage backends.
+#[derive(Debug, Error)]
+pub enum DalError {
+    /// No item is stored under the given id.
+    #[error("Task with id {0} not found")]
+    NotFound(String),
+
+    /// An item is already stored under the id given to `insert_new`.
+    #[error("An item with id {0} already exists")]
+    AlreadyExists(String),
+
+    /// Reading or writing the underlying file failed.
+    #[error("{context}: {source}")]
+    Io {
+        context: &'static str,
+        #[source]
+        source: io::Error,
+    },
+
+    /// Stored data couldn't be serialized or parsed.
+    #[cfg(feature = "serde_json")]
+    #[error("Error processing JSON: {0}")]
+    Serde(#[from] serde_json::Error),
+
+    /// Another process held the store lock for longer than the timeout.
+    #[error("Timed out after {timeout:?} waiting for lock on {}; another process is using the store", path.display())]
+    LockTimeout { path: PathBuf, timeout: Duration },
+
+    /// The SQLite backend reported an error.
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
+    /// The PostgreSQL backend reported an error.
+    #[cfg(feature = "postgres")]
+    #[error("PostgreSQL error: {0}")]
+    Postgres(#[from] sqlx::Error),
+
+    /// A setting (environment variable or option) has an invalid value.
+    #[error("{0}")]
+    Config(String),
+}
+
+impl DalError {
+    /// Builds a closure wrapping an `io::Error` with `context`, for use with `map_err`.
+    pub fn io(context: &'static str) -> impl FnOnce(io::Error) -> DalError {
+        move |source| DalError::Io { context, source }
+    }
+}
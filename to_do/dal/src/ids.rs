@@ -0,0 +1,191 @@
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::errors::DalError;
+
+/// Source of unique identifiers for stored items.
+///
+/// Implementations must be safe to share between threads and must never
+/// hand out the same identifier twice for the same store.
+pub trait IdGenerator: Send + Sync {
+    /// Returns a new identifier.
+    fn next_id(&self) -> String;
+}
+
+/// Generates time-ordered UUIDv7 identifiers.
+///
+/// Identifiers sort lexicographically in creation order, which keeps
+/// listings and future database indexes stable.
+pub struct UuidV7;
+
+impl IdGenerator for UuidV7 {
+    fn next_id(&self) -> String {
+        uuid::Uuid::now_v7().to_string()
+    }
+}
+
+/// Generates short random URL-friendly identifiers.
+pub struct NanoId {
+    pub length: usize,
+}
+
+impl Default for NanoId {
+    fn default() -> Self {
+        NanoId { length: 10 }
+    }
+}
+
+impl IdGenerator for NanoId {
+    fn next_id(&self) -> String {
+        nanoid::nanoid!(self.length)
+    }
+}
+
+/// Generates increasing integer identifiers ("1", "2", "3", ...).
+///
+/// The counter is atomic, so a single generator can be shared between
+/// threads without handing out duplicates.
+pub struct Sequential {
+    last: AtomicU64,
+}
+
+impl Sequential {
+    /// Creates a generator whose first identifier will be `last + 1`.
+    pub fn new(last: u64) -> Self {
+        Sequential { last: AtomicU64::new(last) }
+    }
+
+    /// Creates a generator that continues after the highest numeric id
+    /// among `ids`. Non-numeric ids are ignored.
+    ///
+    /// Other processes may take ids after `ids` was read, so store new items
+    /// with `Storage::insert_new` and move on to the next id when it fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dal::ids::{IdGenerator, Sequential};
+    ///
+    /// let ids = Sequential::resume(["1", "7", "abc"]);
+    /// assert_eq!(ids.next_id(), "8");
+    /// ```
+    pub fn resume<'a>(ids: impl IntoIterator<Item = &'a str>) -> Self {
+        let last = ids
+            .into_iter()
+            .filter_map(|id| id.parse::<u64>().ok())
+            .max()
+            .unwrap_or(0);
+        Sequential::new(last)
+    }
+}
+
+impl IdGenerator for Sequential {
+    fn next_id(&self) -> String {
+        (self.last.fetch_add(1, Ordering::SeqCst) + 1).to_string()
+    }
+}
+
+/// The available identifier schemes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdScheme {
+    UuidV7,
+    NanoId,
+    Sequential,
+}
+
+impl IdScheme {
+    /// Parses a scheme name (`uuidv7`, `nanoid` or `sequential`), ignoring case.
+    pub fn from_string(scheme: &str) -> Result<IdScheme, DalError> {
+        match scheme.to_lowercase().as_str() {
+            "uuidv7" | "uuid" => Ok(IdScheme::UuidV7),
+            "nanoid" => Ok(IdScheme::NanoId),
+            "sequential" => Ok(IdScheme::Sequential),
+            _ => Err(DalError::Config(format!("Invalid id scheme: {}", scheme))),
+        }
+    }
+
+    /// Reads the scheme from the `ID_SCHEME` environment variable.
+    /// If not defined, uses UUIDv7.
+    pub fn from_env() -> Result<IdScheme, DalError> {
+        match env::var("ID_SCHEME") {
+            Ok(scheme) => IdScheme::from_string(&scheme),
+            Err(_) => Ok(IdScheme::UuidV7),
+        }
+    }
+
+    /// Builds a generator for this scheme.
+    ///
+    /// # Arguments
+    ///
+    /// * `existing` - Ids already in the store, used to resume the sequential counter
+    pub fn generator<'a>(&self, existing: impl IntoIterator<Item = &'a str>) -> Box<dyn IdGenerator> {
+        match self {
+            IdScheme::UuidV7 => Box::new(UuidV7),
+            IdScheme::NanoId => Box::new(NanoId::default()),
+            IdScheme::Sequential => Box::new(Sequential::resume(existing)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn sequential_ids_resume_after_the_highest_number() {
+        assert_eq!(Sequential::resume(["3", "12", "7"]).next_id(), "13");
+        assert_eq!(Sequential::resume(["007"]).next_id(), "8");
+        assert_eq!(Sequential::resume(std::iter::empty()).next_id(), "1");
+    }
+
+    #[test]
+    fn non_numeric_ids_are_skipped_when_resuming() {
+        let existing = ["01a138f6-3628-70e3-8e69-9605fc9ee47e", "V1StGXR8_Z", "", "-4", "2.5", "5"];
+        let ids = Sequential::resume(existing);
+        assert_eq!(ids.next_id(), "6");
+        assert_eq!(Sequential::resume(["abc", "x1"]).next_id(), "1");
+    }
+
+    #[test]
+    fn shared_sequential_ids_are_never_repeated() {
+        let ids = Arc::new(Sequential::new(0));
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let ids = Arc::clone(&ids);
+                thread::spawn(move || (0..100).map(|_| ids.next_id()).collect::<Vec<_>>())
+            })
+            .collect();
+        let all: Vec<String> = handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect();
+        assert_eq!(all.iter().collect::<HashSet<_>>().len(), 800);
+        assert_eq!(ids.next_id(), "801");
+    }
+
+    #[cfg(feature = "json-file")]
+    #[test]
+    fn ids_taken_after_resuming_are_skipped() {
+        use crate::json_file::JsonFileStore;
+        use crate::storage::Storage;
+
+        let dir = env::temp_dir().join(format!("webtodo-ids-{}-collision", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = JsonFileStore::new(dir.join("tasks.json"));
+        store.save_one("1", &"one".to_string()).unwrap();
+        let ids = Sequential::resume(["1"]);
+        // Another process takes the next two ids in the meantime.
+        store.save_one("2", &"theirs".to_string()).unwrap();
+        store.save_one("3", &"theirs".to_string()).unwrap();
+        let id = loop {
+            let id = ids.next_id();
+            match store.insert_new(&id, &"mine".to_string()) {
+                Err(DalError::AlreadyExists(_)) => continue,
+                result => break result.map(|_| id).unwrap(),
+            }
+        };
+        assert_eq!(id, "4");
+        assert_eq!(Storage::<String>::get_one(&store, "2").unwrap(), "theirs");
+    }
+}
@@ -1,67 +1,838 @@
 use serde::{de::DeserializeOwned, Serialize};
 use std::collections::HashMap;
 use std::env;
-use std::fs::{OpenOptions, File};
-use std::io::{Read, Write};
+use std::fs::{self, OpenOptions, File, TryLockError};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::path::Path;
+use std::process;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
 
-/// Gets a file handle for JSON storage.
-///
-/// Reads the file path from the `JSON_STORE_PATH` environment variable.
-/// If not defined, uses "tasks.json" as the default value.
-/// The file is opened in read/write mode and created if it doesn't exist.
-///
-/// # Returns
+use crate::errors::DalError;
+use crate::storage::{apply_to, FsyncPolicy, Storage};
+
+/// How `save_all` writes the JSON file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteMode {
+    /// Serialize to a temporary file in the same directory and rename it
+    /// over the store, so a crash mid-write leaves the old contents intact.
+    Atomic,
+    /// Truncate and overwrite the store in place. Cheaper, but a crash
+    /// mid-write can leave a partially written file.
+    InPlace,
+    /// Append each single-item change to a `<name>.journal` file as one
+    /// NDJSON line instead of rewriting the store. Reads replay the journal
+    /// over the store; once it outgrows `journal_limit`, it is compacted
+    /// into the store with an atomic write.
+    Journal,
+}
+
+impl WriteMode {
+    /// Parses a write mode name (`atomic`, `in-place` or `journal`), ignoring case.
+    pub fn from_string(mode: &str) -> Result<WriteMode, DalError> {
+        match mode.to_lowercase().as_str() {
+            "atomic" => Ok(WriteMode::Atomic),
+            "in-place" | "inplace" => Ok(WriteMode::InPlace),
+            "journal" => Ok(WriteMode::Journal),
+            _ => Err(DalError::Config(format!("Invalid write mode: {}", mode)))
+        }
+    }
+}
+
+const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_millis(5000);
+const DEFAULT_JOURNAL_LIMIT: u64 = 1024 * 1024;
+
+/// A `Storage` backend keeping all items in a single pretty-printed JSON file.
+pub struct JsonFileStore {
+    pub path: PathBuf,
+    pub write_mode: WriteMode,
+    /// How long to wait for another process to release the store lock.
+    pub lock_timeout: Duration,
+    /// When writes are forced to disk.
+    pub fsync: FsyncPolicy,
+    /// A second file that committed writes are copied to on `flush`.
+    pub replica: Option<PathBuf>,
+    /// Size in bytes past which the journal is compacted into the store.
+    pub journal_limit: u64,
+    sync_state: Mutex<SyncState>,
+}
+
+/// Tracks fsyncs for `FsyncPolicy::Interval` and writes not yet replicated.
+#[derive(Default)]
+struct SyncState {
+    last_sync: Option<Instant>,
+    dirty: bool,
+    replica_dirty: bool,
+}
+
+impl JsonFileStore {
+    /// Creates a store backed by the file at `path`, using atomic writes.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        JsonFileStore {
+            path: path.into(),
+            write_mode: WriteMode::Atomic,
+            lock_timeout: DEFAULT_LOCK_TIMEOUT,
+            fsync: FsyncPolicy::Always,
+            replica: None,
+            journal_limit: DEFAULT_JOURNAL_LIMIT,
+            sync_state: Mutex::new(SyncState::default()),
+        }
+    }
+
+    /// Creates a store using the file path from the `JSON_STORE_PATH`
+    /// environment variable. If not defined, uses "tasks.json".
+    ///
+    /// The write mode is read from `JSON_STORE_WRITE_MODE` (`atomic`,
+    /// `in-place` or `journal`, default atomic). The journal is compacted
+    /// once it exceeds `JSON_STORE_JOURNAL_MAX_BYTES` (default 1 MiB).
+    /// The lock timeout is read from `JSON_STORE_LOCK_TIMEOUT_MS`
+    /// (milliseconds, default 5000), the fsync policy from `STORE_FSYNC`
+    /// (default `always`) and the replica from `JSON_STORE_REPLICA_PATH`
+    /// (default none).
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(JsonFileStore)` - The configured store
+    /// * `Err(DalError::Config)` - If one of the variables is set to something invalid
+    pub fn from_env() -> Result<Self, DalError> {
+        let file_path = env::var("JSON_STORE_PATH").unwrap_or_else(|_| "tasks.json".to_string());
+        let write_mode = match env::var("JSON_STORE_WRITE_MODE") {
+            Ok(mode) => WriteMode::from_string(&mode)?,
+            Err(_) => WriteMode::Atomic,
+        };
+        let lock_timeout = parse_var::<u64>("JSON_STORE_LOCK_TIMEOUT_MS", env::var("JSON_STORE_LOCK_TIMEOUT_MS").ok())?
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_LOCK_TIMEOUT);
+        let fsync = FsyncPolicy::from_env()?;
+        let replica = env::var("JSON_STORE_REPLICA_PATH").ok().filter(|path| !path.is_empty()).map(PathBuf::from);
+        let journal_limit = parse_var("JSON_STORE_JOURNAL_MAX_BYTES", env::var("JSON_STORE_JOURNAL_MAX_BYTES").ok())?
+            .unwrap_or(DEFAULT_JOURNAL_LIMIT);
+        Ok(JsonFileStore { write_mode, lock_timeout, fsync, replica, journal_limit, ..JsonFileStore::new(file_path) })
+    }
+
+    /// The journal kept next to the store, `<name>.journal`.
+    pub fn journal_path(&self) -> PathBuf {
+        let mut journal_name = self.path.file_name().unwrap_or_default().to_os_string();
+        journal_name.push(".journal");
+        self.path.with_file_name(journal_name)
+    }
+
+    /// Folds the journal into the store with an atomic write and removes it.
+    ///
+    /// This happens on its own once the journal outgrows `journal_limit`;
+    /// calling it directly is only needed to shrink a journal sooner.
+    pub fn compact(&self) -> Result<(), DalError> {
+        let _lock = self.lock(true)?;
+        self.compact_locked()
+    }
+
+    /// Rewrites the whole store under the exclusive lock, e.g. to move it to
+    /// a new layout.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Gets every stored item and returns the items to store instead, or `None` to leave the store as it is
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the store was rewritten
+    /// * `Ok(false)` - If `f` left it as it was
+    /// * `Err(E)` - The error from `f`, or from reading or writing the store
+    pub fn rewrite<T, U, E>(&self, f: impl FnOnce(HashMap<String, T>) -> Result<Option<HashMap<String, U>>, E>) -> Result<bool, E>
+    where T: DeserializeOwned, U: Serialize, E: From<DalError> {
+        let _lock = self.lock(true)?;
+        match f(self.read_all::<T>()?)? {
+            Some(tasks) => {
+                self.write_all(&tasks)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn compact_locked(&self) -> Result<(), DalError> {
+        let tasks = self.read_all::<serde_json::Value>()?;
+        let json = serde_json::to_vec_pretty(&tasks)?;
+        self.write_atomic(&json)?;
+        self.clear_journal()
+    }
+
+    /// Gets a file handle for JSON storage.
+    ///
+    /// The file is opened in read/write mode and created if it doesn't exist.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(File)` - Handle to the opened file
+    /// * `Err(DalError::Io)` - If file opening fails
+    fn get_handle(&self) -> Result<File, DalError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)
+            .map_err(DalError::io("Error opening file"))?;
+        Ok(file)
+    }
+
+    /// Acquires an advisory lock on the store, waiting up to `lock_timeout`.
+    ///
+    /// The lock is taken on a sibling `<name>.lock` file rather than on the
+    /// store itself, because atomic writes replace the store's inode. The
+    /// lock is released when the returned handle is dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `exclusive` - Take an exclusive (write) lock instead of a shared (read) one
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(File)` - Handle holding the lock
+    /// * `Err(DalError::LockTimeout)` - If the lock can't be acquired in time
+    fn lock(&self, exclusive: bool) -> Result<File, DalError> {
+        let mut lock_name = self.path.file_name().unwrap_or_default().to_os_string();
+        lock_name.push(".lock");
+        let lock_path = self.path.with_file_name(lock_name);
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&lock_path)
+            .map_err(DalError::io("Error opening lock file"))?;
+        let deadline = Instant::now() + self.lock_timeout;
+        loop {
+            let attempt = if exclusive { file.try_lock() } else { file.try_lock_shared() };
+            match attempt {
+                Ok(()) => return Ok(file),
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                    thread::sleep(Duration::from_millis(20));
+                }
+                Err(TryLockError::WouldBlock) => {
+                    return Err(DalError::LockTimeout { path: lock_path, timeout: self.lock_timeout });
+                }
+                Err(TryLockError::Error(e)) => return Err(DalError::io("Error locking file")(e)),
+            }
+        }
+    }
+
+    /// Streams the stored items one at a time instead of loading the whole
+    /// map, so memory use is bounded by the largest single item.
+    ///
+    /// A shared lock is held until the iterator is dropped, so writers wait
+    /// for the iteration to finish.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Entries<T>)` - Iterator over `(id, item)` pairs, in file order
+    /// * `Err(DalError)` - If the store can't be locked or opened
+    pub fn iter_all<T: DeserializeOwned>(&self) -> Result<Entries<T>, DalError> {
+        // The iterator only reads the store, so fold pending changes into it first.
+        if self.journal_len() > 0 {
+            self.compact()?;
+        }
+        let lock = self.lock(false)?;
+        let file = self.get_handle()?;
+        Ok(Entries { reader: BufReader::new(file), state: EntriesState::Start, _lock: lock, _item: PhantomData })
+    }
+
+    fn read_all<T: DeserializeOwned>(&self) -> Result<HashMap<String, T>, DalError> {
+        let mut file = self.get_handle()?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)
+            .map_err(DalError::io("Error reading file"))?;
+        // A freshly created store is empty rather than an empty JSON object.
+        let mut tasks: HashMap<String, T> = if contents.trim().is_empty() {
+            HashMap::new()
+        } else {
+            serde_json::from_str(&contents)?
+        };
+        // Replayed whatever the write mode, so switching away from journal
+        // mode doesn't lose the changes still in it.
+        self.replay_journal(&mut tasks)?;
+        Ok(tasks)
+    }
+
+    /// Applies the journal's changes, oldest first, to `tasks`.
+    ///
+    /// A last line without a newline is a write that was cut short by a
+    /// crash; it is ignored if it can't be parsed, as it never completed.
+    fn replay_journal<T: DeserializeOwned>(&self, tasks: &mut HashMap<String, T>) -> Result<(), DalError> {
+        let contents = match fs::read_to_string(self.journal_path()) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(DalError::io("Error reading journal")(e)),
+        };
+        let complete = contents.ends_with('\n');
+        let lines: Vec<&str> = contents.lines().collect();
+        for (i, line) in lines.iter().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = match parse_journal_entry::<T>(line) {
+                Ok(entry) => entry,
+                Err(_) if i + 1 == lines.len() && !complete => break,
+                Err(e) => return Err(e),
+            };
+            match entry {
+                (id, Some(item)) => tasks.insert(id, item),
+                (id, None) => tasks.remove(&id),
+            };
+        }
+        Ok(())
+    }
+
+    /// Size of the journal in bytes; 0 if there is none.
+    pub fn journal_len(&self) -> u64 {
+        fs::metadata(self.journal_path()).map_or(0, |metadata| metadata.len())
+    }
+
+    /// Appends one change to the journal, compacting it if it grew past
+    /// `journal_limit`. `item` is `None` for a deletion.
+    ///
+    /// The caller must hold the exclusive lock.
+    fn append_journal<T: Serialize>(&self, id: &str, item: Option<&T>) -> Result<(), DalError> {
+        let entry = match item {
+            Some(item) => serde_json::json!({ "op": "put", "id": id, "item": item }),
+            None => serde_json::json!({ "op": "delete", "id": id }),
+        };
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        let journal = self.journal_path();
+        let created = !journal.exists();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .create(true)
+            .append(true)
+            .open(&journal)
+            .map_err(DalError::io("Error opening journal"))?;
+        drop_torn_tail(&mut file).map_err(DalError::io("Error repairing journal"))?;
+        // A single write, so a crash can only cut off the end of this line.
+        file.write_all(&line).map_err(DalError::io("Error writing to journal"))?;
+        if self.sync_due() {
+            file.sync_data().map_err(DalError::io("Error syncing journal"))?;
+            if created {
+                sync_dir(&journal)?;
+            }
+        }
+        self.mark_replica_dirty();
+        if self.journal_len() > self.journal_limit {
+            self.compact_locked()?;
+        }
+        Ok(())
+    }
+
+    /// Removes the journal after its changes were written to the store.
+    fn clear_journal(&self) -> Result<(), DalError> {
+        match fs::remove_file(self.journal_path()) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(DalError::io("Error removing journal")(e)),
+        }
+    }
+
+    fn write_all<T: Serialize>(&self, tasks: &HashMap<String, T>) -> Result<(), DalError> {
+        let json = serde_json::to_string_pretty(tasks)?;
+        match self.write_mode {
+            WriteMode::Atomic | WriteMode::Journal => self.write_atomic(json.as_bytes())?,
+            WriteMode::InPlace => self.write_in_place(json.as_bytes())?,
+        }
+        // `tasks` already includes anything the journal held (it was read
+        // through `read_all`), so replaying it again would undo newer changes.
+        self.clear_journal()?;
+        self.mark_replica_dirty();
+        Ok(())
+    }
+
+    fn mark_replica_dirty(&self) {
+        if self.replica.is_some() {
+            self.sync_state.lock().unwrap_or_else(|e| e.into_inner()).replica_dirty = true;
+        }
+    }
+
+    /// Copies the store, as of a moment no write was in progress, to `dest`
+    /// with an atomic, fsynced write.
+    fn copy_to(&self, dest: &Path) -> Result<(), DalError> {
+        // A shared lock keeps writers out while still letting readers in.
+        let _lock = self.lock(false)?;
+        let mut contents = Vec::new();
+        if self.journal_len() > 0 {
+            // The copy is a plain store, so it takes the journal's changes with it.
+            contents = serde_json::to_vec_pretty(&self.read_all::<serde_json::Value>()?)?;
+        } else {
+            self.get_handle()?
+                .read_to_end(&mut contents)
+                .map_err(DalError::io("Error reading file"))?;
+        }
+        JsonFileStore::new(dest).write_atomic(&contents)
+    }
+
+    /// Decides whether the write about to happen must be fsynced, according
+    /// to the policy. Writes that aren't synced mark the store as dirty.
+    fn sync_due(&self) -> bool {
+        let mut state = self.sync_state.lock().unwrap_or_else(|e| e.into_inner());
+        let due = match self.fsync {
+            FsyncPolicy::Always => true,
+            FsyncPolicy::Never => false,
+            FsyncPolicy::Interval(interval) => state.last_sync.is_none_or(|t| t.elapsed() >= interval),
+        };
+        if due {
+            state.last_sync = Some(Instant::now());
+            state.dirty = false;
+        } else {
+            state.dirty = true;
+        }
+        due
+    }
+
+    /// Writes `contents` to a sibling temporary file and renames it over the
+    /// store. When a sync is due, the file is fsynced before the rename and
+    /// the directory after it, so the new contents survive power loss.
+    ///
+    /// If the store is a symlink, the file it points to is replaced and the
+    /// link kept. The new file gets the old one's permissions.
+    fn write_atomic(&self, contents: &[u8]) -> Result<(), DalError> {
+        let sync = self.sync_due();
+        let target = resolve_symlinks(&self.path);
+        let mut tmp_name = target.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(format!(".{}.tmp", process::id()));
+        let tmp_path = target.with_file_name(tmp_name);
+        let permissions = fs::metadata(&target).ok().map(|metadata| metadata.permissions());
+        let result = File::create(&tmp_path)
+            .and_then(|mut file| {
+                if let Some(permissions) = permissions {
+                    file.set_permissions(permissions)?;
+                }
+                file.write_all(contents)?;
+                if sync { file.sync_all() } else { Ok(()) }
+            })
+            .and_then(|_| fs::rename(&tmp_path, &target));
+        if let Err(e) = result {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(DalError::io("Error writing to file")(e));
+        }
+        if sync {
+            sync_dir(&target)?;
+        }
+        Ok(())
+    }
+
+    /// Truncates the store and writes `contents` over it.
+    fn write_in_place(&self, contents: &[u8]) -> Result<(), DalError> {
+        let mut file = self.get_handle()?;
+        file.set_len(0).map_err(DalError::io("Error writing to file"))?;
+        file.write_all(contents).map_err(DalError::io("Error writing to file"))?;
+        if self.sync_due() {
+            file.sync_all().map_err(DalError::io("Error syncing file"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Describes a replica and how far it lags behind the store, judged by the
+/// files' modification times.
+fn replica_status(path: &Path, replica: &Path) -> String {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+    match (modified(path), modified(replica)) {
+        (_, Err(_)) => format!("{} (not written yet)", replica.display()),
+        (Ok(primary), Ok(copy)) => match primary.duration_since(copy) {
+            Ok(lag) if !lag.is_zero() => format!("{} (lagging by {:?})", replica.display(), lag),
+            _ => format!("{} (in sync)", replica.display()),
+        },
+        (Err(_), Ok(_)) => format!("{} (store file missing)", replica.display()),
+    }
+}
+
+/// Iterator returned by `JsonFileStore::iter_all`.
 ///
-/// * `Ok(File)` - Handle to the opened file
-/// * `Err(String)` - Error message if file opening fails
-fn get_handle() -> Result<File, String> {
-    let file_path = env::var("JSON_STORE_PATH").unwrap_or_else(|_| "tasks.json".to_string());
-    let file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .open(&file_path)
-        .map_err(|e| format!("Error opening file: {}", e))?;
-    Ok(file)
+/// It scans the JSON object one entry at a time, buffering only the bytes of
+/// the current value before deserializing it. After the first error it
+/// stops.
+pub struct Entries<T> {
+    reader: BufReader<File>,
+    state: EntriesState,
+    _lock: File,
+    _item: PhantomData<T>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum EntriesState {
+    Start,
+    First,
+    Rest,
+    Done,
+}
+
+impl<T: DeserializeOwned> Iterator for Entries<T> {
+    type Item = Result<(String, T), DalError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.state == EntriesState::Done {
+            return None;
+        }
+        let entry = self.next_entry();
+        if !matches!(entry, Ok(Some(_))) {
+            self.state = EntriesState::Done;
+        }
+        entry.transpose()
+    }
+}
+
+impl<T: DeserializeOwned> Entries<T> {
+    fn next_entry(&mut self) -> Result<Option<(String, T)>, DalError> {
+        if self.state == EntriesState::Start {
+            // An empty file is an empty store, as in `read_all`.
+            match self.next_byte()? {
+                None => return Ok(None),
+                Some(b'{') => self.state = EntriesState::First,
+                Some(_) => return Err(malformed("expected '{' at the start of the store")),
+            }
+        }
+        match (self.next_byte()?, self.state) {
+            (Some(b'}'), _) => {
+                // `read_all` rejects anything after the object, so do the same.
+                return match self.next_byte()? {
+                    None => Ok(None),
+                    Some(_) => Err(malformed("unexpected data after the closing '}'")),
+                };
+            }
+            (Some(b'"'), EntriesState::First) => {}
+            (Some(b','), EntriesState::Rest) => {
+                if self.next_byte()? != Some(b'"') {
+                    return Err(malformed("expected a key after ','"));
+                }
+            }
+            _ => return Err(malformed("expected a key or '}'")),
+        }
+        self.state = EntriesState::Rest;
+        let mut key = vec![b'"'];
+        self.read_string(&mut key)?;
+        let key: String = serde_json::from_slice(&key)?;
+        if self.next_byte()? != Some(b':') {
+            return Err(malformed("expected ':' after a key"));
+        }
+        let value = self.read_value()?;
+        Ok(Some((key, serde_json::from_slice(&value)?)))
+    }
+
+    /// Returns the next byte that isn't whitespace, or `None` at the end.
+    fn next_byte(&mut self) -> Result<Option<u8>, DalError> {
+        loop {
+            match self.peek()? {
+                Some(byte) if byte.is_ascii_whitespace() => self.reader.consume(1),
+                Some(byte) => {
+                    self.reader.consume(1);
+                    return Ok(Some(byte));
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, DalError> {
+        let buf = self.reader.fill_buf().map_err(DalError::io("Error reading file"))?;
+        Ok(buf.first().copied())
+    }
+
+    /// Appends the rest of a string whose opening quote was already read,
+    /// up to and including the closing quote.
+    fn read_string(&mut self, out: &mut Vec<u8>) -> Result<(), DalError> {
+        let mut escaped = false;
+        loop {
+            let byte = self.peek()?.ok_or_else(|| malformed("unterminated string"))?;
+            self.reader.consume(1);
+            out.push(byte);
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+
+    /// Reads one complete JSON value: an object or array up to its matching
+    /// bracket, a string, or a scalar up to the next delimiter.
+    fn read_value(&mut self) -> Result<Vec<u8>, DalError> {
+        let first = self.next_byte()?.ok_or_else(|| malformed("expected a value"))?;
+        let mut out = vec![first];
+        match first {
+            b'"' => self.read_string(&mut out)?,
+            b'{' | b'[' => {
+                let mut depth = 1;
+                while depth > 0 {
+                    let byte = self.peek()?.ok_or_else(|| malformed("unterminated value"))?;
+                    self.reader.consume(1);
+                    out.push(byte);
+                    match byte {
+                        b'"' => self.read_string(&mut out)?,
+                        b'{' | b'[' => depth += 1,
+                        b'}' | b']' => depth -= 1,
+                        _ => {}
+                    }
+                }
+            }
+            _ => {
+                while let Some(byte) = self.peek()? {
+                    if byte == b',' || byte == b'}' || byte.is_ascii_whitespace() {
+                        break;
+                    }
+                    self.reader.consume(1);
+                    out.push(byte);
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Cuts off a last journal line that a crash left without its newline, so
+/// the next entry starts on a line of its own.
+fn drop_torn_tail(file: &mut File) -> io::Result<()> {
+    let len = file.metadata()?.len();
+    if len == 0 {
+        return Ok(());
+    }
+    let mut last = [0u8];
+    file.seek(SeekFrom::Start(len - 1))?;
+    file.read_exact(&mut last)?;
+    if last[0] == b'\n' {
+        return Ok(());
+    }
+    let mut contents = Vec::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_end(&mut contents)?;
+    let keep = contents.iter().rposition(|byte| *byte == b'\n').map_or(0, |i| i + 1);
+    file.set_len(keep as u64)
+}
+
+/// Parses one journal line into the changed id and its new value, or `None`
+/// for a deletion.
+fn parse_journal_entry<T: DeserializeOwned>(line: &str) -> Result<(String, Option<T>), DalError> {
+    let mut entry: serde_json::Value = serde_json::from_str(line)?;
+    let id = match entry.get("id").and_then(|id| id.as_str()) {
+        Some(id) => id.to_string(),
+        None => return Err(malformed("journal entry without an id")),
+    };
+    match entry.get("op").and_then(|op| op.as_str()) {
+        Some("put") => Ok((id, Some(serde_json::from_value(entry["item"].take())?))),
+        Some("delete") => Ok((id, None)),
+        _ => Err(malformed("journal entry with an unknown op")),
+    }
+}
+
+fn malformed(message: &str) -> DalError {
+    DalError::Serde(serde::de::Error::custom(format!("Malformed store: {}", message)))
+}
+
+/// Parses the value of the environment variable `name`, if it's set.
+fn parse_var<T: std::str::FromStr>(name: &str, value: Option<String>) -> Result<Option<T>, DalError> {
+    value
+        .map(|value| value.trim().parse().map_err(|_| DalError::Config(format!("Invalid {}: {}", name, value))))
+        .transpose()
+}
+
+/// Follows `path` through any symlinks to the file they end at, which need
+/// not exist yet. Gives up after 40 links, as the OS would, returning where
+/// it got to.
+fn resolve_symlinks(path: &Path) -> PathBuf {
+    let mut path = path.to_path_buf();
+    for _ in 0..40 {
+        let Ok(link) = fs::read_link(&path) else { break };
+        path = match path.parent() {
+            Some(dir) => dir.join(link),
+            None => link,
+        };
+    }
+    path
+}
+
+/// Fsyncs the directory containing `path`, making a rename into it durable.
+fn sync_dir(path: &Path) -> Result<(), DalError> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .map_err(DalError::io("Error syncing directory"))
+}
+
+impl<T: Serialize + DeserializeOwned> Storage<T> for JsonFileStore {
+    fn get_all(&self) -> Result<HashMap<String, T>, DalError> {
+        let _lock = self.lock(false)?;
+        self.read_all()
+    }
+
+    fn save_all(&self, tasks: &HashMap<String, T>) -> Result<(), DalError> {
+        let _lock = self.lock(true)?;
+        self.write_all(tasks)
+    }
+
+    fn save_one(&self, id: &str, task: &T) -> Result<(), DalError> where T: Clone {
+        let _lock = self.lock(true)?;
+        if self.write_mode == WriteMode::Journal {
+            return self.append_journal(id, Some(task));
+        }
+        let mut tasks = self.read_all()?;
+        tasks.insert(id.to_string(), task.clone());
+        self.write_all(&tasks)
+    }
+
+    fn insert_new(&self, id: &str, task: &T) -> Result<(), DalError> where T: Clone {
+        let _lock = self.lock(true)?;
+        let mut tasks = self.read_all::<T>()?;
+        if tasks.contains_key(id) {
+            return Err(DalError::AlreadyExists(id.to_string()));
+        }
+        if self.write_mode == WriteMode::Journal {
+            return self.append_journal(id, Some(task));
+        }
+        tasks.insert(id.to_string(), task.clone());
+        self.write_all(&tasks)
+    }
+
+    fn delete_one(&self, id: &str) -> Result<(), DalError> {
+        let _lock = self.lock(true)?;
+        if self.write_mode == WriteMode::Journal {
+            return self.append_journal::<T>(id, None);
+        }
+        let mut tasks = self.read_all::<T>()?;
+        tasks.remove(id);
+        self.write_all(&tasks)
+    }
+
+    /// Rewrites the store once for all the changes. In journal mode a single
+    /// change is appended as usual, while several are folded into the store
+    /// together with the journal, so a crash can't keep only some of them.
+    fn apply_changes(&self, changes: &[(String, Option<T>)]) -> Result<(), DalError> where T: Clone {
+        let _lock = self.lock(true)?;
+        if let (WriteMode::Journal, [(id, task)]) = (self.write_mode, changes) {
+            return self.append_journal(id, task.as_ref());
+        }
+        let mut tasks = self.read_all::<T>()?;
+        apply_to(&mut tasks, changes);
+        self.write_all(&tasks)
+    }
+
+    /// Fsyncs writes deferred by the fsync policy, then brings the replica,
+    /// if one is configured, up to date with the store.
+    fn flush(&self) -> Result<(), DalError> {
+        let mut state = self.sync_state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.dirty && self.fsync != FsyncPolicy::Never {
+            File::open(&self.path)
+                .and_then(|file| file.sync_all())
+                .map_err(DalError::io("Error syncing file"))?;
+            let journal = self.journal_path();
+            if journal.exists() {
+                File::open(&journal)
+                    .and_then(|file| file.sync_all())
+                    .map_err(DalError::io("Error syncing journal"))?;
+            }
+            sync_dir(&self.path)?;
+            state.last_sync = Some(Instant::now());
+            state.dirty = false;
+        }
+        if let Some(replica) = &self.replica
+            && state.replica_dirty
+        {
+            self.copy_to(replica)?;
+            state.replica_dirty = false;
+        }
+        Ok(())
+    }
+
+    fn durability(&self) -> String {
+        let write = match self.write_mode {
+            WriteMode::Atomic => "writes replace the file atomically, so a crash leaves either the old or the new contents",
+            WriteMode::InPlace => "writes overwrite the file in place, so a crash mid-write can leave it corrupt",
+            WriteMode::Journal => "changes are appended to a journal that is replayed on read, so a crash loses at most the change being written",
+        };
+        let sync = match self.fsync {
+            FsyncPolicy::Always => "every write is fsynced before it returns".to_string(),
+            FsyncPolicy::Interval(interval) => format!(
+                "writes are fsynced at most every {:?} and on exit; power loss can drop writes from the last interval",
+                interval
+            ),
+            FsyncPolicy::Never => "writes are never fsynced; power loss can drop any recent write".to_string(),
+        };
+        format!("{}; {}", write, sync)
+    }
+
+    fn snapshot(&self, dest: &Path) -> Result<(), DalError> {
+        self.copy_to(dest)
+    }
+
+    fn diagnostics(&self) -> Result<Vec<(&'static str, String)>, DalError> {
+        let size = match fs::metadata(&self.path) {
+            Ok(metadata) => format!("{} bytes", metadata.len()),
+            Err(_) => "missing (created on first write)".to_string(),
+        };
+        let write_mode = match self.write_mode {
+            WriteMode::Atomic => "atomic",
+            WriteMode::InPlace => "in-place",
+            WriteMode::Journal => "journal",
+        };
+        let compaction = match (self.write_mode, self.journal_len()) {
+            (WriteMode::Journal, len) => format!(
+                "{} holds {} bytes; compacted into the store past {} bytes",
+                self.journal_path().display(), len, self.journal_limit,
+            ),
+            (_, 0) => "not needed (the file is rewritten on every save)".to_string(),
+            (_, len) => format!("{} holds {} bytes; folded into the store on the next write", self.journal_path().display(), len),
+        };
+        let replica = match &self.replica {
+            Some(replica) => replica_status(&self.path, replica),
+            None => "none (set JSON_STORE_REPLICA_PATH to enable)".to_string(),
+        };
+        Ok(vec![
+            ("Backend", "json-file".to_string()),
+            ("Path", self.path.display().to_string()),
+            ("File size", size),
+            ("Write mode", write_mode.to_string()),
+            ("Lock timeout", format!("{:?}", self.lock_timeout)),
+            ("Schema", "unversioned (one JSON object keyed by id)".to_string()),
+            ("Compaction", compaction),
+            ("Replica", replica),
+        ])
+    }
 }
 
 /// Retrieves all items stored in the JSON file.
 ///
 /// Reads the JSON file content and deserializes it into a HashMap
 /// where the key is a String (usually an ID), and the value is of generic type T.
+/// Uses the file given by `JSON_STORE_PATH` (see `JsonFileStore::from_env`).
 ///
 /// # Type Parameters
 ///
-/// * `T` - Type of items to deserialize. Must implement `DeserializeOwned`
+/// * `T` - Type of items to deserialize. Must implement `Serialize` and `DeserializeOwned`
 ///
 /// # Returns
 ///
 /// * `Ok(HashMap<String, T>)` - Map with all stored items
-/// * `Err(String)` - Error message if reading or JSON parsing fails
+/// * `Err(DalError)` - `Io` if reading fails, `Serde` if the JSON can't be parsed
 ///
 /// # Examples
 ///
-/// ```
+/// ```ignore
 /// let tasks: HashMap<String, Task> = get_all().unwrap();
 /// ```
-pub fn get_all<T: DeserializeOwned>() -> Result<HashMap<String, T>, String>{
-    let mut file = get_handle()?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)
-        .map_err(|e| format!("Error reading file: {}", e))?;
-    let tasks: HashMap<String, T> = serde_json::from_str(&contents)
-        .map_err(|e| format!("Error parsing JSON: {}", e))?;
-    Ok(tasks)
+pub fn get_all<T: Serialize + DeserializeOwned>() -> Result<HashMap<String, T>, DalError>{
+    Storage::<T>::get_all(&JsonFileStore::from_env()?)
 }
 
 /// Saves all items to the JSON file.
 ///
 /// Serializes the complete HashMap to JSON with readable format (pretty-print)
-/// and overwrites the file content.
+/// and replaces the file content, atomically unless `JSON_STORE_WRITE_MODE=in-place`.
 ///
 /// # Type Parameters
 ///
-/// * `T` - Type of items to serialize. Must implement `Serialize`
+/// * `T` - Type of items to serialize. Must implement `Serialize` and `DeserializeOwned`
 ///
 /// # Arguments
 ///
@@ -70,20 +841,17 @@ pub fn get_all<T: DeserializeOwned>() -> Result<HashMap<String, T>, String>{
 /// # Returns
 ///
 /// * `Ok(())` - If the operation was successful
-/// * `Err(String)` - Error message if serialization or writing fails
+/// * `Err(DalError)` - `Serde` if serialization fails, `Io` if writing fails
 ///
 /// # Examples
 ///
-/// ```
+/// ```ignore
 /// let mut tasks = HashMap::new();
 /// tasks.insert("1".to_string(), my_task);
 /// save_all(&tasks).unwrap();
 /// ```
-pub fn save_all<T: Serialize>(tasks: &HashMap<String, T>) -> Result<(), String>{
-    let mut file = get_handle()?;
-    let json = serde_json::to_string_pretty(tasks).map_err(|e| format!("Error serializing JSON: {}", e))?;
-    file.write_all(json.as_bytes()).map_err(|e| format!("Error writing to file: {}", e))?;
-    Ok(())
+pub fn save_all<T: Serialize + DeserializeOwned>(tasks: &HashMap<String, T>) -> Result<(), DalError>{
+    JsonFileStore::from_env()?.save_all(tasks)
 }
 
 /// Retrieves a single item from JSON storage by its ID.
@@ -93,7 +861,7 @@ pub fn save_all<T: Serialize>(tasks: &HashMap<String, T>) -> Result<(), String>{
 ///
 /// # Type Parameters
 ///
-/// * `T` - Type of item to retrieve. Must implement `DeserializeOwned` and `Clone`
+/// * `T` - Type of item to retrieve. Must implement `Serialize`, `DeserializeOwned` and `Clone`
 ///
 /// # Arguments
 ///
@@ -102,26 +870,23 @@ pub fn save_all<T: Serialize>(tasks: &HashMap<String, T>) -> Result<(), String>{
 /// # Returns
 ///
 /// * `Ok(T)` - The found item
-/// * `Err(String)` - Error message if the item is not found or reading fails
+/// * `Err(DalError)` - `NotFound` if no item has this ID, otherwise the reading error
 ///
 /// # Examples
 ///
-/// ```
+/// ```ignore
 /// let task: Task = get_one("123").unwrap();
 /// ```
-pub fn get_one<T: DeserializeOwned + Clone>(id: &str) -> Result<T, String>{
-    let tasks = get_all::<T>()?;
-    match tasks.get(id) {
-        Some(t) => Ok(t.clone()),
-        None => Err(format!("Task with id {} not found", id))
-    }
+pub fn get_one<T: Serialize + DeserializeOwned + Clone>(id: &str) -> Result<T, DalError>{
+    Storage::<T>::get_one(&JsonFileStore::from_env()?, id)
 }
 
 /// Saves a single item to JSON storage.
 ///
 /// Updates or inserts an item in the JSON file. If the ID already exists,
 /// the item is updated; if it doesn't exist, a new one is created.
-/// If the file doesn't exist or is empty, a new HashMap is created.
+/// If the file doesn't exist or is empty, a new HashMap is created; a file
+/// that can't be parsed is reported instead of being overwritten.
 ///
 /// # Type Parameters
 ///
@@ -135,18 +900,16 @@ pub fn get_one<T: DeserializeOwned + Clone>(id: &str) -> Result<T, String>{
 /// # Returns
 ///
 /// * `Ok(())` - If the operation was successful
-/// * `Err(String)` - Error message if the operation fails
+/// * `Err(DalError)` - Error if reading or writing the store fails
 ///
 /// # Examples
 ///
-/// ```
+/// ```ignore
 /// let task = Task::new("My task");
 /// save_one("123", &task).unwrap();
 /// ```
-pub fn save_one<T>(id: &str, task: &T) -> Result<(), String> where T: Serialize + DeserializeOwned + Clone {
-    let mut tasks = get_all::<T>().unwrap_or_else(|_| HashMap::new());
-    tasks.insert(id.to_string(), task.clone());
-    save_all(&tasks)
+pub fn save_one<T>(id: &str, task: &T) -> Result<(), DalError> where T: Serialize + DeserializeOwned + Clone {
+    JsonFileStore::from_env()?.save_one(id, task)
 }
 
 
@@ -166,15 +929,252 @@ pub fn save_one<T>(id: &str, task: &T) -> Result<(), String> where T: Serialize
 /// # Returns
 ///
 /// * `Ok(())` - If the operation was successful
-/// * `Err(String)` - Error message if the operation fails
+/// * `Err(DalError)` - Error if reading or writing the store fails
 ///
 /// # Example
 ///
-/// ```
+/// ```ignore
 /// delete_one::<Task>("123").unwrap();
 /// ```
-pub fn delete_one<T>(id: &str) -> Result<(), String> where T: Serialize + DeserializeOwned + Clone {
-    let mut tasks = get_all::<T>().unwrap_or_else(|_| HashMap::new());
-    tasks.remove(id);
-    save_all(&tasks)
-}
\ No newline at end of file
+pub fn delete_one<T>(id: &str) -> Result<(), DalError> where T: Serialize + DeserializeOwned + Clone {
+    Storage::<T>::delete_one(&JsonFileStore::from_env()?, id)
+}
+
+/// Streams the items stored in the JSON file one at a time.
+///
+/// Uses the file given by `JSON_STORE_PATH` (see `JsonFileStore::iter_all`).
+/// If the store can't be opened, the iterator yields that error once.
+///
+/// # Type Parameters
+///
+/// * `T` - Type of items to deserialize. Must implement `DeserializeOwned`
+///
+/// # Examples
+///
+/// ```ignore
+/// for entry in iter_all::<Task>() {
+///     let (id, task) = entry?;
+///     println!("{} {}", id, task.title);
+/// }
+/// ```
+pub fn iter_all<T: DeserializeOwned>() -> impl Iterator<Item = Result<(String, T), DalError>> {
+    let (entries, error) = match JsonFileStore::from_env().and_then(|store| store.iter_all()) {
+        Ok(entries) => (Some(entries), None),
+        Err(e) => (None, Some(Err(e))),
+    };
+    error.into_iter().chain(entries.into_iter().flatten())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    /// A store in a fresh file under the temp directory, named after the test.
+    fn temp_store(name: &str) -> JsonFileStore {
+        let dir = env::temp_dir().join(format!("webtodo-json-file-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        JsonFileStore::new(dir.join("tasks.json"))
+    }
+
+    fn entries(store: &JsonFileStore) -> Result<Vec<(String, Value)>, DalError> {
+        store.iter_all::<Value>()?.collect()
+    }
+
+    #[test]
+    fn iter_all_matches_read_all() {
+        let store = temp_store("iter-all");
+        let contents = r#"{
+            "plain": {"title": "Buy milk", "tags": ["a", "b"]},
+            "quo\"te": "back\\slash \"quoted\" }{ ][",
+            "nested": {"a": {"b": [1, {"c": "}"}]}},
+            "unicode é": [true, false, null, -1.5e3],
+            "empty": {}
+        }"#;
+        fs::write(&store.path, contents).unwrap();
+        let streamed: HashMap<String, Value> = entries(&store).unwrap().into_iter().collect();
+        let read: HashMap<String, Value> = Storage::get_all(&store).unwrap();
+        assert_eq!(streamed, read);
+        assert_eq!(streamed["quo\"te"], json!("back\\slash \"quoted\" }{ ]["));
+        assert_eq!(streamed["unicode é"], json!([true, false, null, -1500.0]));
+    }
+
+    #[test]
+    fn iter_all_of_an_empty_file_is_empty() {
+        let store = temp_store("iter-empty");
+        fs::write(&store.path, "").unwrap();
+        assert!(entries(&store).unwrap().is_empty());
+        fs::write(&store.path, " {\n} \n").unwrap();
+        assert!(entries(&store).unwrap().is_empty());
+    }
+
+    #[test]
+    fn iter_all_rejects_truncated_input() {
+        let store = temp_store("iter-truncated");
+        let contents = serde_json::to_string_pretty(&json!({
+            "1": {"title": "a \"b\" c", "n": 12},
+            "2": ["x", {"y": null}],
+            "3": "three",
+        })).unwrap();
+        for len in 1..contents.len() {
+            fs::write(&store.path, &contents[..len]).unwrap();
+            assert!(entries(&store).is_err(), "prefix of {} bytes was accepted: {:?}", len, &contents[..len]);
+        }
+    }
+
+    #[test]
+    fn iter_all_rejects_trailing_data() {
+        let store = temp_store("iter-trailing");
+        for contents in [r#"{"a": 1} x"#, r#"{"a": 1}{}"#, r#"{} ,"#] {
+            fs::write(&store.path, contents).unwrap();
+            assert!(entries(&store).is_err(), "{:?} was accepted", contents);
+            assert!(Storage::<Value>::get_all(&store).is_err(), "read_all accepted {:?}", contents);
+        }
+    }
+
+    #[test]
+    fn iter_all_rejects_malformed_input() {
+        let store = temp_store("iter-malformed");
+        for contents in ["[]", r#"{"a" 1}"#, r#"{"a": 1 "b": 2}"#, r#"{,"a": 1}"#, r#"{"a": 1,}"#, r#"{a: 1}"#] {
+            fs::write(&store.path, contents).unwrap();
+            assert!(entries(&store).is_err(), "{:?} was accepted", contents);
+        }
+    }
+
+    #[test]
+    fn insert_new_never_overwrites() {
+        for mode in [WriteMode::Atomic, WriteMode::Journal] {
+            let mut store = temp_store(&format!("insert-new-{:?}", mode));
+            store.write_mode = mode;
+            store.insert_new("1", &json!("one")).unwrap();
+            assert!(matches!(store.insert_new("1", &json!("uno")), Err(DalError::AlreadyExists(id)) if id == "1"));
+            assert_eq!(Storage::<Value>::get_one(&store, "1").unwrap(), json!("one"));
+        }
+    }
+
+    #[test]
+    fn rewrite_replaces_the_store_unless_told_not_to() {
+        let store = temp_store("rewrite");
+        store.save_one("coding", &json!("PENDING")).unwrap();
+        let unchanged = store.rewrite(|_: HashMap<String, Value>| Ok::<_, DalError>(None::<HashMap<String, Value>>)).unwrap();
+        assert!(!unchanged);
+        let rewritten = store.rewrite(|old: HashMap<String, Value>| {
+            Ok::<_, DalError>(Some(old.into_iter().map(|(title, status)| ("1".to_string(), json!({ "title": title, "status": status }))).collect::<HashMap<_, _>>()))
+        }).unwrap();
+        assert!(rewritten);
+        assert_eq!(entries(&store).unwrap(), vec![("1".to_string(), json!({ "title": "coding", "status": "PENDING" }))]);
+    }
+
+    #[test]
+    fn journal_is_replayed_over_the_store() {
+        let mut store = temp_store("journal-replay");
+        store.write_mode = WriteMode::Journal;
+        store.save_one("1", &json!("one")).unwrap();
+        store.save_one("2", &json!("two")).unwrap();
+        store.save_one("1", &json!("uno")).unwrap();
+        Storage::<Value>::delete_one(&store, "2").unwrap();
+        assert!(store.journal_len() > 0);
+        let tasks: HashMap<String, Value> = Storage::get_all(&store).unwrap();
+        assert_eq!(tasks, HashMap::from([("1".to_string(), json!("uno"))]));
+    }
+
+    #[test]
+    fn torn_last_journal_line_is_ignored_and_dropped() {
+        let mut store = temp_store("journal-torn");
+        store.write_mode = WriteMode::Journal;
+        store.save_one("1", &json!("one")).unwrap();
+        let mut journal = OpenOptions::new().append(true).open(store.journal_path()).unwrap();
+        journal.write_all(br#"{"op":"put","id":"2","it"#).unwrap();
+        drop(journal);
+        let tasks: HashMap<String, Value> = Storage::get_all(&store).unwrap();
+        assert_eq!(tasks, HashMap::from([("1".to_string(), json!("one"))]));
+        // The next append must not be glued onto the torn line.
+        store.save_one("3", &json!("three")).unwrap();
+        let tasks: HashMap<String, Value> = Storage::get_all(&store).unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks["3"], json!("three"));
+        assert!(fs::read_to_string(store.journal_path()).unwrap().lines().all(|line| parse_journal_entry::<Value>(line).is_ok()));
+    }
+
+    #[test]
+    fn corrupt_journal_line_before_the_last_is_an_error() {
+        let store = temp_store("journal-corrupt");
+        fs::write(store.journal_path(), "{\"op\":\"put\",\"id\":\"1\",\"item\":1}\nnot json\n").unwrap();
+        assert!(Storage::<Value>::get_all(&store).is_err());
+        fs::write(store.journal_path(), "{\"op\":\"move\",\"id\":\"1\"}\n").unwrap();
+        assert!(Storage::<Value>::get_all(&store).is_err());
+    }
+
+    #[test]
+    fn journal_is_compacted_past_its_limit() {
+        let mut store = temp_store("journal-compact");
+        store.write_mode = WriteMode::Journal;
+        store.journal_limit = 200;
+        for i in 0..20 {
+            store.save_one(&i.to_string(), &json!({ "title": format!("task {}", i) })).unwrap();
+        }
+        assert!(store.journal_len() <= store.journal_limit);
+        let tasks: HashMap<String, Value> = Storage::get_all(&store).unwrap();
+        assert_eq!(tasks.len(), 20);
+        store.compact().unwrap();
+        assert_eq!(store.journal_len(), 0);
+        assert_eq!(entries(&store).unwrap().len(), 20);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn atomic_writes_keep_the_mode_and_symlinks() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+        let store = temp_store("atomic-mode");
+        store.save_one("1", &json!("one")).unwrap();
+        fs::set_permissions(&store.path, fs::Permissions::from_mode(0o600)).unwrap();
+        store.save_one("2", &json!("two")).unwrap();
+        assert_eq!(fs::metadata(&store.path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        let link = JsonFileStore::new(store.path.with_file_name("link.json"));
+        symlink("tasks.json", &link.path).unwrap();
+        link.save_one("3", &json!("three")).unwrap();
+        assert!(fs::symlink_metadata(&link.path).unwrap().file_type().is_symlink());
+        assert_eq!(entries(&store).unwrap().len(), 3);
+        assert_eq!(fs::metadata(&store.path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    #[test]
+    fn temp_files_left_by_a_crash_are_ignored_and_replaced() {
+        let store = temp_store("atomic-leftover");
+        store.save_one("1", &json!("one")).unwrap();
+        let leftover = |pid: u32| store.path.with_file_name(format!("tasks.json.{}.tmp", pid));
+        // One from another process, and one from an earlier process with this id.
+        fs::write(leftover(process::id().wrapping_add(1)), "{\"torn").unwrap();
+        fs::write(leftover(process::id()), "{\"1\": \"stale\", \"2\": \"stale\"}").unwrap();
+        assert_eq!(entries(&store).unwrap(), vec![("1".to_string(), json!("one"))]);
+        store.save_one("3", &json!("three")).unwrap();
+        let tasks: HashMap<String, Value> = Storage::get_all(&store).unwrap();
+        assert_eq!(tasks, HashMap::from([("1".to_string(), json!("one")), ("3".to_string(), json!("three"))]));
+        assert!(!leftover(process::id()).exists());
+    }
+
+    #[test]
+    fn a_held_lock_times_out() {
+        let mut store = temp_store("lock-timeout");
+        store.lock_timeout = Duration::from_millis(100);
+        store.save_one("1", &json!("one")).unwrap();
+        let held = store.lock(true).unwrap();
+        let started = Instant::now();
+        assert!(matches!(store.save_one("2", &json!("two")), Err(DalError::LockTimeout { timeout, .. }) if timeout == store.lock_timeout));
+        assert!(started.elapsed() >= store.lock_timeout);
+        drop(held);
+        store.save_one("2", &json!("two")).unwrap();
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        assert_eq!(parse_var::<u64>("JSON_STORE_LOCK_TIMEOUT_MS", None).unwrap(), None);
+        assert_eq!(parse_var::<u64>("JSON_STORE_LOCK_TIMEOUT_MS", Some(" 250 ".to_string())).unwrap(), Some(250));
+        for value in ["", "5s", "-1"] {
+            assert!(matches!(parse_var::<u64>("JSON_STORE_LOCK_TIMEOUT_MS", Some(value.to_string())), Err(DalError::Config(_))));
+        }
+        assert!(matches!(WriteMode::from_string("apend"), Err(DalError::Config(_))));
+    }
+}
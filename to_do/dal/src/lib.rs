@@ -1,2 +1,13 @@
 #[cfg(feature = "json-file")]
 pub mod json_file;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod cached;
+#[cfg(feature = "json-file")]
+pub mod oplog;
+pub mod errors;
+pub mod ids;
+pub mod storage;
+pub mod tracked;
@@ -0,0 +1,208 @@
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+
+use crate::errors::DalError;
+use crate::json_file::JsonFileStore;
+
+const DEFAULT_LIMIT: usize = 50;
+
+/// Which of the two stacks of an `OpLog` to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stack {
+    /// Operations that can be undone, newest last.
+    Undo,
+    /// Undone operations that can be redone, newest last.
+    Redo,
+}
+
+impl Stack {
+    fn key(self) -> &'static str {
+        match self {
+            Stack::Undo => "undo",
+            Stack::Redo => "redo",
+        }
+    }
+}
+
+/// A small log of recent operations, kept as an undo and a redo stack.
+///
+/// For file-based stores the stacks live in a JSON file next to the store,
+/// read and written through a `JsonFileStore`, so they get the same locking
+/// and atomic writes as a JSON store. For PostgreSQL they live in the
+/// database's `oplog` table, so every client of a shared store sees the same
+/// log. The operations are opaque to the log: any type that serializes can
+/// be pushed and popped.
+pub struct OpLog {
+    backend: Backend,
+    /// How many operations the undo stack keeps; older ones are dropped.
+    pub limit: usize,
+}
+
+enum Backend {
+    File(JsonFileStore),
+    #[cfg(feature = "postgres")]
+    Postgres(crate::postgres::PostgresStore),
+}
+
+impl OpLog {
+    /// Creates a log kept in the file at `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        OpLog { backend: Backend::File(JsonFileStore::new(path)), limit: DEFAULT_LIMIT }
+    }
+
+    /// Creates a log at `STORE_HISTORY_PATH`. If not defined, it sits next to
+    /// the store file as `<name>.history` (e.g. `tasks.json.history`), or in
+    /// the `oplog` table for PostgreSQL. `STORE_HISTORY_LIMIT` sets how many
+    /// operations are kept (default 50).
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(OpLog)` - The log
+    /// * `Err(DalError)` - If the PostgreSQL database can't be reached
+    pub fn from_env() -> Result<Self, DalError> {
+        let limit = env::var("STORE_HISTORY_LIMIT")
+            .ok()
+            .and_then(|limit| limit.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_LIMIT);
+        if let Some(path) = env::var("STORE_HISTORY_PATH").ok().filter(|path| !path.is_empty()) {
+            return Ok(OpLog { limit, ..OpLog::new(path) });
+        }
+        let backend = env::var("STORE_BACKEND").unwrap_or_else(|_| "json-file".to_string());
+        let path = match backend.to_lowercase().as_str() {
+            #[cfg(feature = "postgres")]
+            "postgres" => {
+                let store = crate::postgres::PostgresStore::from_env()?;
+                return Ok(OpLog { backend: Backend::Postgres(store), limit });
+            }
+            "sqlite" => format!("{}.history", env::var("SQLITE_STORE_PATH").unwrap_or_else(|_| "tasks.db".to_string())),
+            _ => format!("{}.history", env::var("JSON_STORE_PATH").unwrap_or_else(|_| "tasks.json".to_string())),
+        };
+        Ok(OpLog { limit, ..OpLog::new(path) })
+    }
+
+    /// Runs `f` on both stacks, keyed by `Stack::key`, and stores what it
+    /// leaves, all under one lock (one transaction for PostgreSQL), so
+    /// concurrent commands never lose each other's operations.
+    fn update<R>(&self, f: impl FnOnce(&mut HashMap<String, Vec<Value>>) -> R) -> Result<R, DalError> {
+        match &self.backend {
+            Backend::File(store) => {
+                let mut result = None;
+                store.rewrite(|mut stacks: HashMap<String, Vec<Value>>| {
+                    result = Some(f(&mut stacks));
+                    Ok::<_, DalError>(Some(stacks))
+                })?;
+                Ok(result.expect("rewrite runs its closure unless it fails"))
+            }
+            #[cfg(feature = "postgres")]
+            Backend::Postgres(store) => {
+                store.runtime.block_on(store.update_log(&[Stack::Undo.key(), Stack::Redo.key()], f))
+            }
+        }
+    }
+
+    /// Records a new operation: it goes on the undo stack, and the redo
+    /// stack is cleared, since what it held no longer follows from the
+    /// current state. Both stacks change in the same write.
+    pub fn record<T: Serialize + DeserializeOwned + Clone>(&self, op: &T) -> Result<(), DalError> {
+        let op = serde_json::to_value(op)?;
+        self.update(|stacks| {
+            self.push_value(stacks, Stack::Undo, op);
+            stacks.insert(Stack::Redo.key().to_string(), Vec::new());
+        })
+    }
+
+    /// Puts `op` on top of `stack`, dropping the oldest operations past `limit`.
+    pub fn push<T: Serialize + DeserializeOwned + Clone>(&self, stack: Stack, op: &T) -> Result<(), DalError> {
+        let op = serde_json::to_value(op)?;
+        self.update(|stacks| self.push_value(stacks, stack, op))
+    }
+
+    fn push_value(&self, stacks: &mut HashMap<String, Vec<Value>>, stack: Stack, op: Value) {
+        let ops = stacks.entry(stack.key().to_string()).or_default();
+        ops.push(op);
+        if ops.len() > self.limit {
+            ops.drain(..ops.len() - self.limit);
+        }
+    }
+
+    /// Takes the newest operation off `stack`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(T))` - The operation
+    /// * `Ok(None)` - If the stack is empty
+    /// * `Err(DalError)` - Error if reading or writing the log fails
+    pub fn pop<T: Serialize + DeserializeOwned + Clone>(&self, stack: Stack) -> Result<Option<T>, DalError> {
+        // Only taken off once it parses, so a bad entry isn't lost.
+        let op = self.update(|stacks| {
+            let ops = stacks.get_mut(stack.key())?;
+            let op = serde_json::from_value::<T>(ops.last()?.clone());
+            if op.is_ok() {
+                ops.pop();
+            }
+            Some(op)
+        })?;
+        Ok(op.transpose()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, process, thread};
+
+    /// A log file in a fresh directory under the temp directory, named after the test.
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("webtodo-oplog-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("tasks.json.history")
+    }
+
+    #[test]
+    fn record_pushes_undo_and_clears_redo() {
+        let log = OpLog::new(temp_path("record"));
+        log.record(&"one".to_string()).unwrap();
+        log.push(Stack::Redo, &"undone".to_string()).unwrap();
+        log.record(&"two".to_string()).unwrap();
+        assert_eq!(log.pop::<String>(Stack::Redo).unwrap(), None);
+        assert_eq!(log.pop::<String>(Stack::Undo).unwrap().as_deref(), Some("two"));
+        assert_eq!(log.pop::<String>(Stack::Undo).unwrap().as_deref(), Some("one"));
+        assert_eq!(log.pop::<String>(Stack::Undo).unwrap(), None);
+    }
+
+    #[test]
+    fn only_the_newest_operations_are_kept() {
+        let log = OpLog { limit: 2, ..OpLog::new(temp_path("limit")) };
+        for op in ["one", "two", "three"] {
+            log.record(&op.to_string()).unwrap();
+        }
+        assert_eq!(log.pop::<String>(Stack::Undo).unwrap().as_deref(), Some("three"));
+        assert_eq!(log.pop::<String>(Stack::Undo).unwrap().as_deref(), Some("two"));
+        assert_eq!(log.pop::<String>(Stack::Undo).unwrap(), None);
+    }
+
+    #[test]
+    fn concurrent_records_are_all_kept() {
+        let path = temp_path("concurrent");
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                thread::spawn(move || OpLog::new(path).record(&i).unwrap())
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let log = OpLog::new(path);
+        let mut ops = Vec::new();
+        while let Some(op) = log.pop::<i32>(Stack::Undo).unwrap() {
+            ops.push(op);
+        }
+        ops.sort();
+        assert_eq!(ops, (0..8).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,263 @@
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::errors::DalError;
+use crate::storage::{FsyncPolicy, Page, Storage, Tagged};
+
+/// How long a statement waits for another connection's lock before failing
+/// with "database is locked".
+const BUSY_TIMEOUT: Duration = Duration::from_millis(5000);
+
+/// A `Storage` backend keeping items in an SQLite database.
+///
+/// Items are stored as JSON text in a single `items(id, data)` table, so
+/// any serializable type can be stored without a dedicated schema.
+pub struct SqliteStore {
+    conn: Connection,
+    path: PathBuf,
+    fsync: FsyncPolicy,
+}
+
+impl SqliteStore {
+    /// Opens (or creates) the database at `path` and ensures the schema exists.
+    ///
+    /// The fsync policy maps onto SQLite's `synchronous` pragma: `Always` is
+    /// `FULL`, `Interval` is `NORMAL` and `Never` is `OFF`. The database is
+    /// put in WAL mode, so reads don't block the writer, and a write waits up
+    /// to `BUSY_TIMEOUT` for another process's write to finish.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(SqliteStore)` - Store ready for use
+    /// * `Err(DalError::Sqlite)` - If the database can't be opened or initialized
+    pub fn open(path: impl AsRef<Path>, fsync: FsyncPolicy) -> Result<Self, DalError> {
+        let path = path.as_ref().to_path_buf();
+        let conn = Connection::open(&path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        let synchronous = match fsync {
+            FsyncPolicy::Always => "FULL",
+            FsyncPolicy::Interval(_) => "NORMAL",
+            FsyncPolicy::Never => "OFF",
+        };
+        conn.pragma_update(None, "synchronous", synchronous)?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS items (id TEXT PRIMARY KEY, data TEXT NOT NULL)",
+            [],
+        )?;
+        Ok(SqliteStore { conn, path, fsync })
+    }
+
+    /// Opens the database from the `SQLITE_STORE_PATH` environment variable,
+    /// with the fsync policy from `STORE_FSYNC`. If not defined, uses "tasks.db".
+    pub fn from_env() -> Result<Self, DalError> {
+        let path = env::var("SQLITE_STORE_PATH").unwrap_or_else(|_| "tasks.db".to_string());
+        SqliteStore::open(path, FsyncPolicy::from_env()?)
+    }
+}
+
+impl SqliteStore {
+    /// Runs a query selecting `(id, data)` rows and deserializes the data.
+    fn query_items<T: DeserializeOwned>(&self, sql: &str, params: impl rusqlite::Params) -> Result<HashMap<String, T>, DalError> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt.query_map(params, |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        let mut tasks = HashMap::new();
+        for row in rows {
+            let (id, data) = row?;
+            tasks.insert(id, serde_json::from_str(&data)?);
+        }
+        Ok(tasks)
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Storage<T> for SqliteStore {
+    fn get_all(&self) -> Result<HashMap<String, T>, DalError> {
+        self.query_items("SELECT id, data FROM items", [])
+    }
+
+    fn get_page(&self, offset: usize, limit: usize) -> Result<Page<T>, DalError> {
+        let total: i64 = self.conn.query_row("SELECT count(*) FROM items", [], |row| row.get(0))?;
+        let mut stmt = self.conn.prepare("SELECT id, data FROM items ORDER BY id LIMIT ?1 OFFSET ?2")?;
+        let rows = stmt.query_map(
+            params![i64::try_from(limit).unwrap_or(i64::MAX), i64::try_from(offset).unwrap_or(i64::MAX)],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )?;
+        let mut items = Vec::new();
+        for row in rows {
+            let (id, data) = row?;
+            items.push((id, serde_json::from_str(&data)?));
+        }
+        Ok(Page { items, total: usize::try_from(total).unwrap_or(0) })
+    }
+
+    fn get_by_tag(&self, tag: &str) -> Result<HashMap<String, T>, DalError> where T: Tagged {
+        self.query_items(
+            "SELECT id, data FROM items WHERE EXISTS (SELECT 1 FROM json_each(items.data, '$.tags') WHERE value = ?1)",
+            params![tag],
+        )
+    }
+
+    fn save_all(&self, tasks: &HashMap<String, T>) -> Result<(), DalError> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM items", [])?;
+        for (id, task) in tasks {
+            tx.execute("INSERT INTO items (id, data) VALUES (?1, ?2)", params![id, serde_json::to_string(task)?])?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_one(&self, id: &str) -> Result<T, DalError> {
+        let data: Option<String> = self.conn
+            .query_row("SELECT data FROM items WHERE id = ?1", params![id], |row| row.get(0))
+            .optional()?;
+        match data {
+            Some(data) => Ok(serde_json::from_str(&data)?),
+            None => Err(DalError::NotFound(id.to_string()))
+        }
+    }
+
+    fn save_one(&self, id: &str, task: &T) -> Result<(), DalError> where T: Clone {
+        self.conn.execute(
+            "INSERT INTO items (id, data) VALUES (?1, ?2) ON CONFLICT(id) DO UPDATE SET data = excluded.data",
+            params![id, serde_json::to_string(task)?],
+        )?;
+        Ok(())
+    }
+
+    fn insert_new(&self, id: &str, task: &T) -> Result<(), DalError> where T: Clone {
+        let inserted = self.conn.execute(
+            "INSERT INTO items (id, data) VALUES (?1, ?2) ON CONFLICT(id) DO NOTHING",
+            params![id, serde_json::to_string(task)?],
+        )?;
+        if inserted == 0 {
+            return Err(DalError::AlreadyExists(id.to_string()));
+        }
+        Ok(())
+    }
+
+    fn delete_one(&self, id: &str) -> Result<(), DalError> {
+        self.conn.execute("DELETE FROM items WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    fn apply_changes(&self, changes: &[(String, Option<T>)]) -> Result<(), DalError> where T: Clone {
+        let tx = self.conn.unchecked_transaction()?;
+        for (id, task) in changes {
+            match task {
+                Some(task) => tx.execute(
+                    "INSERT INTO items (id, data) VALUES (?1, ?2) ON CONFLICT(id) DO UPDATE SET data = excluded.data",
+                    params![id, serde_json::to_string(task)?],
+                )?,
+                None => tx.execute("DELETE FROM items WHERE id = ?1", params![id])?,
+            };
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn durability(&self) -> String {
+        let sync = match self.fsync {
+            FsyncPolicy::Always => "synchronous=FULL: a committed write survives power loss",
+            FsyncPolicy::Interval(_) => "synchronous=NORMAL: a committed write survives a crash; power loss can drop the latest ones",
+            FsyncPolicy::Never => "synchronous=OFF: power loss can drop or corrupt recent writes",
+        };
+        format!("every write is an SQLite transaction (WAL mode); {}", sync)
+    }
+
+    fn snapshot(&self, dest: &Path) -> Result<(), DalError> {
+        // VACUUM INTO reads from a single transaction, so the copy is
+        // consistent even while other connections keep writing.
+        let dest = dest.to_str()
+            .ok_or_else(|| DalError::Config(format!("Snapshot path is not valid UTF-8: {}", dest.display())))?;
+        self.conn.execute("VACUUM INTO ?1", params![dest])?;
+        Ok(())
+    }
+
+    fn diagnostics(&self) -> Result<Vec<(&'static str, String)>, DalError> {
+        let size = fs::metadata(&self.path)
+            .map(|metadata| format!("{} bytes", metadata.len()))
+            .map_err(DalError::io("Error reading file metadata"))?;
+        let version: i64 = self.conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        let free_pages: i64 = self.conn.pragma_query_value(None, "freelist_count", |row| row.get(0))?;
+        let integrity: String = self.conn.pragma_query_value(None, "quick_check", |row| row.get(0))?;
+        Ok(vec![
+            ("Backend", "sqlite".to_string()),
+            ("Path", self.path.display().to_string()),
+            ("File size", size),
+            ("Schema version", version.to_string()),
+            ("Free pages", format!("{} (reclaimed by VACUUM)", free_pages)),
+            ("Integrity", integrity),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::process;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Item {
+        title: String,
+        tags: Vec<String>,
+    }
+
+    impl Tagged for Item {
+        fn tags(&self) -> &[String] {
+            &self.tags
+        }
+    }
+
+    fn item(title: &str, tags: &[&str]) -> Item {
+        Item { title: title.to_string(), tags: tags.iter().map(|tag| tag.to_string()).collect() }
+    }
+
+    fn temp_store(name: &str) -> SqliteStore {
+        let dir = env::temp_dir().join(format!("webtodo-sqlite-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        SqliteStore::open(dir.join("tasks.db"), FsyncPolicy::Never).unwrap()
+    }
+
+    #[test]
+    fn items_round_trip() {
+        let store = temp_store("round-trip");
+        store.save_one("1", &item("one", &["home"])).unwrap();
+        store.save_one("1", &item("uno", &["home", "work"])).unwrap();
+        store.insert_new("2", &item("two", &["work"])).unwrap();
+        assert!(matches!(store.insert_new("2", &item("dos", &[])), Err(DalError::AlreadyExists(id)) if id == "2"));
+        assert_eq!(Storage::<Item>::get_one(&store, "1").unwrap(), item("uno", &["home", "work"]));
+        assert_eq!(Storage::<Item>::get_one(&store, "2").unwrap(), item("two", &["work"]));
+
+        let home = store.get_by_tag("home").unwrap();
+        assert_eq!(home, HashMap::from([("1".to_string(), item("uno", &["home", "work"]))]));
+        assert_eq!(Storage::<Item>::get_by_tag(&store, "work").unwrap().len(), 2);
+
+        Storage::<Item>::delete_one(&store, "1").unwrap();
+        assert!(matches!(Storage::<Item>::get_one(&store, "1"), Err(DalError::NotFound(id)) if id == "1"));
+        assert_eq!(Storage::<Item>::get_all(&store).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn pages_are_in_id_order() {
+        let store = temp_store("pages");
+        for id in ["c", "a", "e", "b", "d"] {
+            store.save_one(id, &item(id, &[])).unwrap();
+        }
+        let ids = |page: Page<Item>| page.items.into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+        let first = Storage::<Item>::get_page(&store, 0, 2).unwrap();
+        assert_eq!(first.total, 5);
+        assert_eq!(ids(first), ["a", "b"]);
+        assert_eq!(ids(Storage::<Item>::get_page(&store, 4, 2).unwrap()), ["e"]);
+        let past_the_end = Storage::<Item>::get_page(&store, 10, 2).unwrap();
+        assert_eq!(past_the_end.total, 5);
+        assert!(past_the_end.items.is_empty());
+    }
+}
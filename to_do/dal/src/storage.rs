@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+use std::time::Duration;
+
+#[cfg(any(feature = "json-file", feature = "sqlite", feature = "postgres"))]
+use crate::cached::{self, CachedStore};
+use crate::errors::DalError;
+
+/// When a backend forces written data to stable storage (fsync).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    /// Every write is flushed to disk before it returns.
+    Always,
+    /// Writes are flushed at most once per interval; anything newer is
+    /// flushed by `Storage::flush` (the CLI calls it on exit).
+    Interval(Duration),
+    /// Never flush explicitly; the operating system decides when data
+    /// reaches the disk.
+    Never,
+}
+
+impl FsyncPolicy {
+    /// Parses `always`, `never`, `interval` (one second) or `interval:<ms>`.
+    pub fn from_string(policy: &str) -> Result<FsyncPolicy, DalError> {
+        let policy = policy.to_lowercase();
+        match policy.as_str() {
+            "always" => Ok(FsyncPolicy::Always),
+            "never" => Ok(FsyncPolicy::Never),
+            "interval" => Ok(FsyncPolicy::Interval(Duration::from_secs(1))),
+            _ => match policy.strip_prefix("interval:").and_then(|ms| ms.parse::<u64>().ok()) {
+                Some(ms) => Ok(FsyncPolicy::Interval(Duration::from_millis(ms))),
+                None => Err(DalError::Config(format!("Invalid fsync policy: {}", policy)))
+            }
+        }
+    }
+
+    /// Reads the policy from the `STORE_FSYNC` environment variable.
+    /// If not defined, uses `Always`.
+    pub fn from_env() -> Result<FsyncPolicy, DalError> {
+        match env::var("STORE_FSYNC") {
+            Ok(policy) => FsyncPolicy::from_string(&policy),
+            Err(_) => Ok(FsyncPolicy::Always),
+        }
+    }
+}
+
+/// One page of items, in id order, from `Storage::get_page`.
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    /// The items on this page as (id, item) pairs.
+    pub items: Vec<(String, T)>,
+    /// How many items the store holds in total.
+    pub total: usize,
+}
+
+/// Items that carry tags, so a backend can filter them by tag.
+///
+/// Implementors must serialize their tags as a `tags` array of strings;
+/// database backends match on that field directly.
+pub trait Tagged {
+    /// The item's tags.
+    fn tags(&self) -> &[String];
+}
+
+/// A backend that persists items of type `T` keyed by a string id.
+///
+/// Backends only have to implement `get_all` and `save_all`; the single-item
+/// operations have default implementations on top of them, which backends
+/// able to address one item directly (e.g. a database) should override.
+///
+/// The trait is object safe, so callers can hold a `Box<dyn Storage<T>>`
+/// and choose the backend at runtime.
+///
+/// # Type Parameters
+///
+/// * `T` - Type of the stored items
+pub trait Storage<T> {
+    /// Retrieves all stored items.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(HashMap<String, T>)` - Map with all stored items
+    /// * `Err(DalError)` - Error if reading the store fails
+    fn get_all(&self) -> Result<HashMap<String, T>, DalError>;
+
+    /// Replaces the stored items with `tasks`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the operation was successful
+    /// * `Err(DalError)` - Error if writing the store fails
+    fn save_all(&self, tasks: &HashMap<String, T>) -> Result<(), DalError>;
+
+    /// Retrieves a single item by its id.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(T)` - The found item
+    /// * `Err(DalError)` - `NotFound` if no item has this id, otherwise the reading error
+    fn get_one(&self, id: &str) -> Result<T, DalError> {
+        let mut tasks = self.get_all()?;
+        match tasks.remove(id) {
+            Some(t) => Ok(t),
+            None => Err(DalError::NotFound(id.to_string()))
+        }
+    }
+
+    /// Inserts or updates a single item.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the operation was successful
+    /// * `Err(DalError)` - Error if reading or writing the store fails
+    fn save_one(&self, id: &str, task: &T) -> Result<(), DalError> where T: Clone {
+        let mut tasks = self.get_all()?;
+        tasks.insert(id.to_string(), task.clone());
+        self.save_all(&tasks)
+    }
+
+    /// Inserts an item under an id that must not be taken yet. The check and
+    /// the write happen under one lock or statement, so two processes can
+    /// never both insert the same id; backends that can't guarantee that
+    /// must override this.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the item was inserted
+    /// * `Err(DalError)` - `AlreadyExists` if the id is taken, otherwise the reading or writing error
+    fn insert_new(&self, id: &str, task: &T) -> Result<(), DalError> where T: Clone {
+        let mut tasks = self.get_all()?;
+        if tasks.contains_key(id) {
+            return Err(DalError::AlreadyExists(id.to_string()));
+        }
+        tasks.insert(id.to_string(), task.clone());
+        self.save_all(&tasks)
+    }
+
+    /// Deletes a single item. Deleting a missing id is not an error.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the operation was successful
+    /// * `Err(DalError)` - Error if reading or writing the store fails
+    fn delete_one(&self, id: &str) -> Result<(), DalError> {
+        let mut tasks = self.get_all()?;
+        tasks.remove(id);
+        self.save_all(&tasks)
+    }
+
+    /// Applies several changes as one write: each id is saved with its new
+    /// item, or deleted when it's `None`. Backends that can should apply
+    /// them all or none, under a single lock or transaction.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If every change was applied
+    /// * `Err(DalError)` - Error if reading or writing the store fails
+    fn apply_changes(&self, changes: &[(String, Option<T>)]) -> Result<(), DalError> where T: Clone {
+        let mut tasks = self.get_all()?;
+        apply_to(&mut tasks, changes);
+        self.save_all(&tasks)
+    }
+
+    /// Retrieves up to `limit` items after skipping the first `offset`, in
+    /// ascending id order (byte-wise), without loading the rest where the
+    /// backend allows it.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Page<T>)` - The items on the page and the total item count
+    /// * `Err(DalError)` - Error if reading the store fails
+    fn get_page(&self, offset: usize, limit: usize) -> Result<Page<T>, DalError> {
+        Ok(page_of(self.get_all()?, offset, limit))
+    }
+
+    /// Retrieves the items carrying `tag`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(HashMap<String, T>)` - Map with the matching items
+    /// * `Err(DalError)` - Error if reading the store fails
+    fn get_by_tag(&self, tag: &str) -> Result<HashMap<String, T>, DalError> where T: Tagged {
+        let mut tasks = self.get_all()?;
+        tasks.retain(|_, task| task.tags().iter().any(|t| t == tag));
+        Ok(tasks)
+    }
+
+    /// Forces any buffered or not yet fsynced writes to stable storage.
+    ///
+    /// Callers should invoke it before exiting. Backends that are durable
+    /// on every write keep the default no-op.
+    fn flush(&self) -> Result<(), DalError> {
+        Ok(())
+    }
+
+    /// Describes what this backend guarantees about data that a write has
+    /// returned for, as configured. Meant for diagnostics such as `store info`.
+    fn durability(&self) -> String {
+        "unspecified".to_string()
+    }
+
+    /// Writes a consistent copy of the whole store to `dest` while other
+    /// processes may keep using it. Concurrent writes are held back, or the
+    /// backend's own snapshot mechanism is used, so the copy never contains
+    /// a half-applied write.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the snapshot was written
+    /// * `Err(DalError)` - `Config` if the backend can't snapshot itself, otherwise the writing error
+    fn snapshot(&self, _dest: &Path) -> Result<(), DalError> {
+        Err(DalError::Config("This backend doesn't support snapshots".to_string()))
+    }
+
+    /// Backend-specific facts as labelled values, such as the backend name,
+    /// where it keeps its data and its schema version. Meant for diagnostics
+    /// such as `store info`; secrets like passwords must be redacted.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<(&str, String)>)` - Label and value pairs, in display order
+    /// * `Err(DalError)` - Error if the backend can't be inspected
+    fn diagnostics(&self) -> Result<Vec<(&'static str, String)>, DalError> {
+        Ok(Vec::new())
+    }
+}
+
+/// Applies `changes` (see `Storage::apply_changes`) to an in-memory map.
+pub fn apply_to<T: Clone>(tasks: &mut HashMap<String, T>, changes: &[(String, Option<T>)]) {
+    for (id, task) in changes {
+        match task {
+            Some(task) => tasks.insert(id.clone(), task.clone()),
+            None => tasks.remove(id),
+        };
+    }
+}
+
+/// Cuts a page (see `Storage::get_page`) out of all the items.
+pub fn page_of<T>(tasks: HashMap<String, T>, offset: usize, limit: usize) -> Page<T> {
+    let total = tasks.len();
+    let mut items: Vec<(String, T)> = tasks.into_iter().collect();
+    items.sort_by(|a, b| a.0.cmp(&b.0));
+    let items = items.into_iter().skip(offset).take(limit).collect();
+    Page { items, total }
+}
+
+/// Opens the backend selected by the `STORE_BACKEND` environment variable.
+///
+/// Accepted values are `json-file`, `sqlite` and `postgres`, depending on the enabled
+/// features. If not defined, uses the JSON file backend.
+///
+/// Unless `STORE_CACHE=off`, the backend is wrapped in a `CachedStore`, so
+/// callers must call `flush` before exiting for their writes to be stored.
+///
+/// # Returns
+///
+/// * `Ok(Box<dyn Storage<T>>)` - The opened backend
+/// * `Err(DalError)` - `Config` if the backend is unknown, or the error opening it
+#[cfg(any(feature = "json-file", feature = "sqlite", feature = "postgres"))]
+pub fn from_env<T>() -> Result<Box<dyn Storage<T>>, DalError>
+where T: serde::Serialize + serde::de::DeserializeOwned + Clone + 'static {
+    // Reject a bad STORE_FSYNC or STORE_CACHE up front rather than silently ignoring it.
+    FsyncPolicy::from_env()?;
+    let cached = cached::enabled_from_env()?;
+    let backend = env::var("STORE_BACKEND").unwrap_or_else(|_| "json-file".to_string());
+    let store: Box<dyn Storage<T>> = match backend.to_lowercase().as_str() {
+        #[cfg(feature = "json-file")]
+        "json-file" | "json" => Box::new(crate::json_file::JsonFileStore::from_env()?),
+        #[cfg(feature = "sqlite")]
+        "sqlite" => Box::new(crate::sqlite::SqliteStore::from_env()?),
+        #[cfg(feature = "postgres")]
+        "postgres" => Box::new(crate::postgres::PostgresStore::from_env()?),
+        _ => return Err(DalError::Config(format!("Unsupported storage backend: {}", backend)))
+    };
+    Ok(if cached { Box::new(CachedStore::new(store)) } else { store })
+}
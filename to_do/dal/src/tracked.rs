@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::errors::DalError;
+use crate::storage::{Page, Storage, Tagged};
+
+/// Passes everything through to another store, remembering which ids were
+/// written and what each held before the first write to it.
+///
+/// It tells a caller exactly what one command changed without comparing
+/// snapshots of the whole store, which would also pick up whatever other
+/// processes changed at the same time.
+///
+/// # Type Parameters
+///
+/// * `T` - Type of the stored items
+pub struct TrackedStore<'a, T: Clone> {
+    inner: &'a dyn Storage<T>,
+    /// The item each written id held before its first write; `None` if
+    /// there was none.
+    before: Mutex<HashMap<String, Option<T>>>,
+}
+
+impl<'a, T: Clone> TrackedStore<'a, T> {
+    /// Wraps `inner`; nothing is tracked yet.
+    pub fn new(inner: &'a dyn Storage<T>) -> Self {
+        TrackedStore { inner, before: Mutex::new(HashMap::new()) }
+    }
+
+    /// The ids written through this store, each with the item it held
+    /// before the first write (`None` if it didn't exist).
+    pub fn touched(&self) -> HashMap<String, Option<T>> {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Option<T>>> {
+        self.before.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Remembers what `id` holds now, unless it was written before.
+    fn track(&self, id: &str) -> Result<(), DalError> {
+        if self.lock().contains_key(id) {
+            return Ok(());
+        }
+        let current = match self.inner.get_one(id) {
+            Ok(task) => Some(task),
+            Err(DalError::NotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+        self.lock().insert(id.to_string(), current);
+        Ok(())
+    }
+}
+
+impl<T: Clone> Storage<T> for TrackedStore<'_, T> {
+    fn get_all(&self) -> Result<HashMap<String, T>, DalError> {
+        self.inner.get_all()
+    }
+
+    fn save_all(&self, tasks: &HashMap<String, T>) -> Result<(), DalError> {
+        let current = self.inner.get_all()?;
+        let mut before = self.lock();
+        for id in current.keys().chain(tasks.keys()) {
+            before.entry(id.clone()).or_insert_with(|| current.get(id).cloned());
+        }
+        drop(before);
+        self.inner.save_all(tasks)
+    }
+
+    fn get_one(&self, id: &str) -> Result<T, DalError> {
+        self.inner.get_one(id)
+    }
+
+    fn save_one(&self, id: &str, task: &T) -> Result<(), DalError> {
+        self.track(id)?;
+        self.inner.save_one(id, task)
+    }
+
+    fn insert_new(&self, id: &str, task: &T) -> Result<(), DalError> {
+        self.track(id)?;
+        self.inner.insert_new(id, task)
+    }
+
+    fn delete_one(&self, id: &str) -> Result<(), DalError> {
+        self.track(id)?;
+        self.inner.delete_one(id)
+    }
+
+    fn apply_changes(&self, changes: &[(String, Option<T>)]) -> Result<(), DalError> {
+        for (id, _) in changes {
+            self.track(id)?;
+        }
+        self.inner.apply_changes(changes)
+    }
+
+    fn get_page(&self, offset: usize, limit: usize) -> Result<Page<T>, DalError> {
+        self.inner.get_page(offset, limit)
+    }
+
+    fn get_by_tag(&self, tag: &str) -> Result<HashMap<String, T>, DalError> where T: Tagged {
+        self.inner.get_by_tag(tag)
+    }
+
+    fn flush(&self) -> Result<(), DalError> {
+        self.inner.flush()
+    }
+
+    fn durability(&self) -> String {
+        self.inner.durability()
+    }
+
+    fn snapshot(&self, dest: &Path) -> Result<(), DalError> {
+        self.inner.snapshot(dest)
+    }
+
+    fn diagnostics(&self) -> Result<Vec<(&'static str, String)>, DalError> {
+        self.inner.diagnostics()
+    }
+}